@@ -1,38 +1,62 @@
 use crate::client::http::ReqwestGateway;
 use crate::client::poller::FunPayPoller;
+use crate::client::session::{Session, SessionTokens};
 use crate::client::FunpayGateway;
 use crate::config::FunPayConfig;
 use crate::error::FunPayError;
 use crate::events::Event;
 use crate::models::enums::SubcategoryType;
-use crate::models::ids::ChatId;
+use crate::models::ids::{ChatId, OrderId};
+use crate::models::money::Currency;
 use crate::models::{
-    CategoryFilter, CategorySubcategory, MarketOffer, Message, Offer, OfferEditParams,
-    OfferFullParams, OfferSaveRequest, Order, OrderShortcut, Subcategory,
+    AccountBalance, BulkEditOptions, BulkEditOutcome, BulkEditResult, CategoryFilter,
+    CategorySubcategory, ChatHistoryOptions, MarketOffer, Message, Offer, OfferChangeSet,
+    OfferDraft, OfferEditParams, OfferEditResult, OfferFullParams, OfferSaveRequest,
+    OfferSaveResult, Order, OrderShortcut, OrdersFilter, OrdersPage, PriceCalculation,
+    PurchaseShortcut, RaiseRequest, RaiseResponse, RaiseResult, RefundResult, Review,
+    ReviewRequest, ReviewResponse, Subcategory, Transaction, TransactionsFilter, TransactionsPage,
+    UserProfile, WithdrawalRequest, WithdrawalResponse, WithdrawalResult,
 };
+use crate::parsing::dates::{funpay_now, parse_wait_duration};
 use crate::parsing::{
-    parse_category_filters, parse_category_subcategories, parse_market_offers, parse_message_html,
+    parse_balance, parse_category_filters, parse_category_subcategories, parse_market_offers,
     parse_my_offers, parse_offer_edit_params, parse_offer_full_params, parse_order_page,
-    parse_order_secrets, parse_orders_list,
+    parse_order_secrets, parse_orders_list, parse_orders_page, parse_purchases_list,
+    parse_raise_game_id, parse_raise_modal_nodes, parse_runner_messages, parse_transactions_page,
+    parse_user_lot_nodes, parse_user_profile, parse_user_reviews, parse_withdrawal_methods,
+};
+use crate::presence::PresenceOptions;
+use crate::repricer::Repricer;
+use crate::reviews::{ReviewReplyAction, ReviewTemplates};
+use crate::runner::{
+    CPUOnline, ChatNode, RunnerAction, RunnerObject, RunnerObjectResult, RunnerResponse,
 };
 use crate::storage::json::JsonFileStorage;
 use crate::storage::memory::InMemoryStorage;
 use crate::storage::StateStorage;
-use crate::utils::{extract_phpsessid, random_tag};
+use crate::utils::{random_tag, RateLimiter, BOT_MARKER};
+use crate::withdrawal::WithdrawalGuard;
+use chrono::{DateTime, FixedOffset};
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
 use regex::Regex;
+use reqwest::StatusCode;
+use rust_decimal::Decimal;
 use scraper::{Html, Selector};
-use serde_json::{json, to_string, Value};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::future::Future;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::broadcast::{self, Sender};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
 
-#[derive(Debug, Clone)]
-struct AppData {
-    user_id: i64,
-    csrf_token: String,
-}
+/// Upper bound for the raiser's sleep, so a successful raise is followed by
+/// a check that learns the real cooldown and new categories are noticed.
+const RAISE_RECHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How many `lots/calc` checks `seller_price_for` makes before giving up.
+const SELLER_PRICE_CHECKS: usize = 5;
 
 pub struct FunPayAccount {
     gateway: Arc<dyn FunpayGateway>,
@@ -40,14 +64,15 @@ pub struct FunPayAccount {
     user_agent: String,
     pub id: Option<i64>,
     pub username: Option<String>,
-    pub csrf_token: Option<String>,
-    phpsessid: Option<String>,
     locale: Option<String>,
+    session: Option<Arc<Session>>,
     pub events_tx: Sender<Event>,
     sorted_subcategories: HashMap<SubcategoryType, HashMap<i64, Subcategory>>,
     storage: Arc<dyn StateStorage>,
     polling_interval: Duration,
     error_retry_delay: Duration,
+    bot_marker: bool,
+    withdrawal_guard: Option<Arc<WithdrawalGuard>>,
 }
 
 impl fmt::Debug for FunPayAccount {
@@ -66,15 +91,56 @@ pub struct FunPaySender {
     gateway: Arc<dyn FunpayGateway>,
     golden_key: String,
     user_agent: String,
-    csrf_token: String,
-    phpsessid: Option<String>,
+    session: Arc<Session>,
     seller_id: i64,
+    withdrawal_guard: Option<Arc<WithdrawalGuard>>,
+    bot_marker: bool,
 }
 
 impl FunPaySender {
+    /// Prefix sent messages and review replies with [`BOT_MARKER`]. Off
+    /// unless enabled here or through `FunPayConfig::bot_marker`.
+    pub fn with_bot_marker(mut self, enabled: bool) -> Self {
+        self.bot_marker = enabled;
+        self
+    }
+
     pub fn seller_id(&self) -> i64 {
         self.seller_id
     }
+
+    pub fn session(&self) -> &Arc<Session> {
+        &self.session
+    }
+
+    fn outgoing_text(&self, text: &str) -> String {
+        if self.bot_marker {
+            format!("{BOT_MARKER}{text}")
+        } else {
+            text.to_string()
+        }
+    }
+
+    async fn post_runner(
+        &self,
+        objects: &[RunnerObject],
+        action: Option<&RunnerAction>,
+    ) -> Result<RunnerResponse, FunPayError> {
+        self.session
+            .call(|tokens| async move {
+                self.gateway
+                    .post_runner(
+                        &self.golden_key,
+                        &self.user_agent,
+                        &tokens.csrf_token,
+                        tokens.phpsessid.as_deref(),
+                        objects,
+                        action,
+                    )
+                    .await
+            })
+            .await
+    }
 }
 
 impl FunPayAccount {
@@ -121,14 +187,15 @@ impl FunPayAccount {
             user_agent: config.user_agent.clone(),
             id: None,
             username: None,
-            csrf_token: None,
-            phpsessid: None,
             locale: None,
+            session: None,
             events_tx: tx,
             sorted_subcategories: HashMap::new(),
             storage,
             polling_interval: config.polling_interval,
             error_retry_delay: config.error_retry_delay,
+            bot_marker: config.bot_marker,
+            withdrawal_guard: config.withdrawal_guard,
         }
     }
 
@@ -140,68 +207,48 @@ impl FunPayAccount {
         self.get().await
     }
 
+    /// The current csrf token; `None` before `init`. The token is rotated
+    /// on session refreshes, so the value goes stale.
+    #[deprecated(note = "use `FunPaySender::session().tokens()` for the current token")]
+    pub fn csrf_token(&self) -> Option<String> {
+        self.session.as_ref().map(|s| s.tokens().csrf_token)
+    }
+
     pub fn create_sender(&self) -> Result<FunPaySender, FunPayError> {
-        let csrf = self
-            .csrf_token
-            .as_ref()
-            .ok_or(FunPayError::AccountNotInitiated)?
-            .to_string();
+        let session = self
+            .session
+            .clone()
+            .ok_or(FunPayError::AccountNotInitiated)?;
         let seller_id = self.id.ok_or(FunPayError::AccountNotInitiated)?;
         Ok(FunPaySender {
             gateway: self.gateway.clone(),
             golden_key: self.golden_key.clone(),
             user_agent: self.user_agent.clone(),
-            csrf_token: csrf,
-            phpsessid: self.phpsessid.clone(),
+            session,
             seller_id,
+            withdrawal_guard: self.withdrawal_guard.clone(),
+            bot_marker: self.bot_marker,
         })
     }
 
     async fn get(&mut self) -> Result<(), FunPayError> {
-        let (body, set_cookies) = self
-            .gateway
-            .get_home(&self.golden_key, &self.user_agent)
-            .await?;
-        if let Some(sess) = extract_phpsessid(&set_cookies) {
-            self.phpsessid = Some(sess);
-        }
-        let html = Html::parse_document(&body);
-        let sel_body = Selector::parse("body").unwrap();
-        let mut app_data: Option<AppData> = None;
-        if let Some(b) = html.select(&sel_body).next() {
-            if let Some(attr) = b.value().attr("data-app-data") {
-                let v: Value =
-                    serde_json::from_str(attr).map_err(|e| FunPayError::Parse(e.to_string()))?;
-                let user_id = v
-                    .get("userId")
-                    .and_then(|x| x.as_i64())
-                    .ok_or_else(|| FunPayError::Parse(String::from("missing userId")))?;
-                let csrf = v
-                    .get("csrf-token")
-                    .and_then(|x| x.as_str())
-                    .ok_or_else(|| FunPayError::Parse(String::from("missing csrf-token")))?;
-                if let Some(loc) = v.get("locale").and_then(|x| x.as_str()) {
-                    self.locale = Some(loc.to_string());
-                }
-                app_data = Some(AppData {
-                    user_id,
-                    csrf_token: csrf.to_string(),
-                });
-            }
+        let snapshot =
+            Session::login(self.gateway.as_ref(), &self.golden_key, &self.user_agent).await?;
+        if let Some(loc) = snapshot.app_data.locale {
+            self.locale = Some(loc);
         }
-        let sel_uname = Selector::parse("div.user-link-name").unwrap();
-        let username = html
-            .select(&sel_uname)
-            .next()
-            .map(|n| n.text().collect::<String>());
-        if username.is_none() {
-            return Err(FunPayError::Unauthorized);
-        }
-        let app = app_data.ok_or_else(|| FunPayError::Parse(String::from("missing app data")))?;
-        self.id = Some(app.user_id);
-        self.csrf_token = Some(app.csrf_token);
-        self.username = username;
-        self.setup_subcategories(&body);
+        self.id = Some(snapshot.app_data.user_id);
+        self.username = Some(snapshot.username);
+        self.session = Some(Arc::new(Session::new(
+            self.gateway.clone(),
+            self.golden_key.clone(),
+            self.user_agent.clone(),
+            SessionTokens {
+                csrf_token: snapshot.app_data.csrf_token,
+                phpsessid: snapshot.phpsessid,
+            },
+        )));
+        self.setup_subcategories(&snapshot.body);
         Ok(())
     }
 
@@ -264,6 +311,35 @@ impl FunPayAccount {
         }
     }
 
+    /// Spawns a task that keeps raising every category with active offers;
+    /// each raise is reported as `Event::LotsRaised`.
+    pub fn spawn_lots_raiser(&self) -> Result<JoinHandle<()>, FunPayError> {
+        let sender = self.create_sender()?;
+        let events_tx = self.events_tx.clone();
+        let error_retry_delay = self.error_retry_delay;
+        Ok(tokio::spawn(
+            sender.run_lots_raiser(events_tx, error_retry_delay),
+        ))
+    }
+
+    /// Spawns a task that keeps the account shown as online during the
+    /// windows in `options`.
+    pub fn spawn_presence_keeper(
+        &self,
+        options: PresenceOptions,
+    ) -> Result<JoinHandle<()>, FunPayError> {
+        let sender = self.create_sender()?;
+        let error_retry_delay = self.error_retry_delay;
+        Ok(tokio::spawn(
+            sender.run_presence(options, error_retry_delay),
+        ))
+    }
+
+    /// A repricer reporting its changes on this account's event channel.
+    pub fn create_repricer(&self) -> Result<Repricer, FunPayError> {
+        Ok(Repricer::new(self.create_sender()?, self.events_tx.clone()))
+    }
+
     pub async fn start_polling_loop(&mut self) -> Result<(), FunPayError> {
         let poller = FunPayPoller {
             gateway: self.gateway.clone(),
@@ -271,11 +347,10 @@ impl FunPayAccount {
             user_agent: self.user_agent.clone(),
             id: self.id.ok_or(FunPayError::AccountNotInitiated)?,
             username: self.username.clone(),
-            csrf_token: self
-                .csrf_token
+            session: self
+                .session
                 .clone()
                 .ok_or(FunPayError::AccountNotInitiated)?,
-            phpsessid: self.phpsessid.clone(),
             events_tx: self.events_tx.clone(),
             storage: self.storage.clone(),
             polling_interval: self.polling_interval,
@@ -284,123 +359,152 @@ impl FunPayAccount {
             last_order_event_tag: random_tag(),
             last_messages: HashMap::new(),
             last_messages_ids: HashMap::new(),
+            chat_node_tags: HashMap::new(),
             saved_orders: HashMap::new(),
+            saved_purchases: None,
+            purchases_changed: true,
+            purchases_fetched_at: None,
         };
         poller.start().await
     }
 }
 
 impl FunPaySender {
+    /// Sends a text message, prefixed with [`BOT_MARKER`] when the sender has
+    /// it enabled.
     pub async fn send_chat_message(&self, chat_id: &str, content: &str) -> Result<(), FunPayError> {
-        let mut csrf_to_use = self.csrf_token.clone();
-        let mut phpsess_to_use = self.phpsessid.clone();
-        if phpsess_to_use.is_none() {
-            let (body, set_cookies) = self
-                .gateway
-                .get_chat_page(&self.golden_key, &self.user_agent, chat_id)
-                .await?;
-            let html = Html::parse_document(&body);
-            let sel_body = Selector::parse("body").unwrap();
-            if let Some(b) = html.select(&sel_body).next() {
-                if let Some(attr) = b.value().attr("data-app-data") {
-                    if let Ok(v) = serde_json::from_str::<Value>(attr) {
-                        if let Some(csrf) = v.get("csrf-token").and_then(|x| x.as_str()) {
-                            csrf_to_use = csrf.to_string();
-                        }
-                    }
-                }
-            }
-            if let Some(sess) = extract_phpsessid(&set_cookies) {
-                phpsess_to_use = Some(sess);
-            }
+        if self.session.tokens().phpsessid.is_none() {
+            self.session.refresh(self.session.generation()).await?;
+        }
+        let objects = [RunnerObject::ChatNode(ChatNode::new(chat_id))];
+        let action = RunnerAction::ChatMessage {
+            node: chat_id.to_string(),
+            last_message: -1,
+            content: self.outgoing_text(content),
+        };
+        let res = self.post_runner(&objects, Some(&action)).await?;
+        match res.response.and_then(|r| r.error) {
+            Some(error) => Err(FunPayError::RunnerAction(error)),
+            None => Ok(()),
         }
-        let objects_json = to_string(&vec![serde_json::json!({
-            "type": "chat_node",
-            "id": chat_id,
-            "tag": "00000000",
-            "data": {"node": chat_id, "last_message": -1, "content": ""}
-        })])
-        .unwrap();
-        let request_json = json!({
-            "action": "chat_message",
-            "data": {"node": chat_id, "last_message": -1, "content": content}
-        })
-        .to_string();
-        self.gateway
-            .post_runner(
-                &self.golden_key,
-                &self.user_agent,
-                &csrf_to_use,
-                phpsess_to_use.as_deref(),
-                &objects_json,
-                Some(&request_json),
-            )
-            .await
-            .map(|_| ())
     }
 
-    pub async fn get_chat_messages(&self, chat_id: &str) -> Result<Vec<Message>, FunPayError> {
-        let objects_json = to_string(&vec![serde_json::json!({
-            "type": "chat_node",
-            "id": chat_id,
-            "tag": "00000000",
-            "data": {"node": chat_id, "last_message": -1, "content": ""}
-        })])
-        .unwrap();
-
-        let res = self
-            .gateway
-            .post_runner(
-                &self.golden_key,
-                &self.user_agent,
-                &self.csrf_token,
-                self.phpsessid.as_deref(),
-                &objects_json,
-                None,
-            )
+    /// Uploads an image and posts it to the chat as a message attachment.
+    pub async fn send_chat_image(
+        &self,
+        chat_id: &str,
+        image: Vec<u8>,
+        filename: &str,
+    ) -> Result<(), FunPayError> {
+        let image_id = self
+            .session
+            .call(|tokens| {
+                let image = image.clone();
+                async move {
+                    self.gateway
+                        .upload_chat_image(
+                            &self.golden_key,
+                            &self.user_agent,
+                            &tokens.csrf_token,
+                            tokens.phpsessid.as_deref(),
+                            image,
+                            filename,
+                        )
+                        .await
+                }
+            })
             .await?;
+        let objects = [RunnerObject::ChatNode(ChatNode::new(chat_id))];
+        let action = RunnerAction::ChatImage {
+            node: chat_id.to_string(),
+            last_message: -1,
+            image_id,
+        };
+        let res = self.post_runner(&objects, Some(&action)).await?;
+        match res.response.and_then(|r| r.error) {
+            Some(error) => Err(FunPayError::RunnerAction(error)),
+            None => Ok(()),
+        }
+    }
 
-        let objects = res
-            .get("objects")
-            .and_then(|x| x.as_array())
-            .cloned()
+    pub async fn get_chat_messages(&self, chat_id: &str) -> Result<Vec<Message>, FunPayError> {
+        let objects = [RunnerObject::ChatNode(ChatNode::new(chat_id))];
+        let res = self.post_runner(&objects, None).await?;
+        let chat_id = ChatId::from(chat_id);
+        let messages = res
+            .chat_nodes()
+            .find_map(|(_, data)| data)
+            .map(|data| parse_runner_messages(&chat_id, None, &data.messages))
             .unwrap_or_default();
+        Ok(messages)
+    }
 
-        for obj in objects {
-            if obj.get("type").and_then(|x| x.as_str()) != Some("chat_node") {
-                continue;
-            }
+    /// Walks the chat history backwards, newest message first, until FunPay
+    /// has no older pages or one of the `options` stop conditions is hit.
+    pub fn get_chat_history<'a>(
+        &'a self,
+        chat_id: &'a str,
+        options: ChatHistoryOptions,
+    ) -> impl Stream<Item = Result<Message, FunPayError>> + 'a {
+        struct Cursor {
+            before: Option<i64>,
+            emitted: usize,
+            done: bool,
+        }
 
-            let data = match obj.get("data") {
-                Some(d) => d,
-                None => continue,
-            };
+        let cursor = Cursor {
+            before: None,
+            emitted: 0,
+            done: options.limit == Some(0),
+        };
+        stream::try_unfold(cursor, move |mut cursor| {
+            let options = options.clone();
+            async move {
+                if cursor.done {
+                    return Ok::<_, FunPayError>(None);
+                }
+                let page = self
+                    .gateway
+                    .get_chat_history(&self.golden_key, &self.user_agent, chat_id, cursor.before)
+                    .await?;
+                let chat = ChatId::from(chat_id);
+                let mut messages = page
+                    .chat
+                    .map(|data| parse_runner_messages(&chat, None, &data.messages))
+                    .unwrap_or_default();
+                messages.sort_by_key(|m| std::cmp::Reverse(m.id));
+                // Only messages older than the cursor count, so a page that
+                // comes back with the same cursor ends the walk instead of
+                // looping forever.
+                if let Some(before) = cursor.before {
+                    messages.retain(|m| m.id < before);
+                }
+                if messages.is_empty() {
+                    return Ok(None);
+                }
+                cursor.before = messages.last().map(|m| m.id);
 
-            let messages = data
-                .get("messages")
-                .and_then(|x| x.as_array())
-                .cloned()
-                .unwrap_or_default();
-
-            let mut list = Vec::new();
-            for m in messages {
-                let mid = m.get("id").and_then(|x| x.as_i64()).unwrap_or(0);
-                let author_id = m.get("author").and_then(|x| x.as_i64()).unwrap_or(0);
-                let html = m.get("html").and_then(|x| x.as_str()).unwrap_or("");
-                let (text, _image) = parse_message_html(html);
-                list.push(Message {
-                    id: mid,
-                    chat_id: ChatId::from(chat_id.to_string()),
-                    chat_name: None,
-                    text,
-                    interlocutor_id: None,
-                    author_id,
-                });
+                if let Some(until_id) = options.until_id {
+                    if messages.iter().any(|m| m.id <= until_id) {
+                        messages.retain(|m| m.id > until_id);
+                        cursor.done = true;
+                    }
+                }
+                if let Some(limit) = options.limit {
+                    let remaining = limit.saturating_sub(cursor.emitted);
+                    if messages.len() >= remaining {
+                        messages.truncate(remaining);
+                        cursor.done = true;
+                    }
+                }
+                cursor.emitted += messages.len();
+                Ok(Some((
+                    stream::iter(messages.into_iter().map(Ok::<_, FunPayError>)),
+                    cursor,
+                )))
             }
-            return Ok(list);
-        }
-
-        Ok(Vec::new())
+        })
+        .try_flatten()
     }
 
     pub fn get_chat_id_for_user(&self, user_id: i64) -> String {
@@ -426,45 +530,260 @@ impl FunPaySender {
         parse_order_page(&body, order_id)
     }
 
+    /// Replies to the buyer's review of an order, replacing our previous
+    /// reply. Prefixed with [`BOT_MARKER`] when `bot_marker` is enabled.
+    pub async fn reply_to_review(&self, order_id: &str, text: &str) -> Result<(), FunPayError> {
+        let text = self.outgoing_text(text);
+        self.post_review(order_id, Some(&text)).await
+    }
+
+    pub async fn delete_review_reply(&self, order_id: &str) -> Result<(), FunPayError> {
+        self.post_review(order_id, None).await
+    }
+
+    /// Fetches the order and replies to its review according to `templates`.
+    /// Nothing is posted for `Escalate` and `Skip`.
+    pub async fn auto_reply_to_review(
+        &self,
+        order_id: &str,
+        templates: &ReviewTemplates,
+    ) -> Result<ReviewReplyAction, FunPayError> {
+        let order = self.get_order(order_id).await?;
+        let action = templates.action_for(&order);
+        if let ReviewReplyAction::Reply(text) = &action {
+            self.reply_to_review(order_id, text).await?;
+        }
+        Ok(action)
+    }
+
+    async fn post_review(&self, order_id: &str, text: Option<&str>) -> Result<(), FunPayError> {
+        let order_id = order_id.trim_start_matches('#');
+        let res = self
+            .session
+            .call(|tokens| async move {
+                self.gateway
+                    .post_review(ReviewRequest {
+                        golden_key: &self.golden_key,
+                        user_agent: &self.user_agent,
+                        phpsessid: tokens.phpsessid.as_deref(),
+                        csrf: &tokens.csrf_token,
+                        author_id: self.seller_id,
+                        order_id,
+                        text,
+                    })
+                    .await
+            })
+            .await;
+        // Rejected replies come back as a 400 with the usual JSON body.
+        let res: ReviewResponse = match res {
+            Ok(res) => res,
+            Err(FunPayError::RequestFailed { status, body, url })
+                if status == StatusCode::BAD_REQUEST =>
+            {
+                serde_json::from_str(&body).map_err(|_| FunPayError::RequestFailed {
+                    status,
+                    body,
+                    url,
+                })?
+            }
+            Err(e) => return Err(e),
+        };
+        match res.error {
+            Some(error) => Err(FunPayError::ReviewFailed(res.msg.unwrap_or(error))),
+            None => Ok(()),
+        }
+    }
+
+    /// Refunds a paid order to the buyer. A refusal from FunPay (the order
+    /// is already closed or refunded, for example) comes back as
+    /// `FunPayError::RefundFailed`.
+    pub async fn refund_order(&self, order_id: &str) -> Result<RefundResult, FunPayError> {
+        let order_id = order_id.trim_start_matches('#');
+        let res = self
+            .session
+            .call_once(|tokens| async move {
+                self.gateway
+                    .post_order_refund(
+                        &self.golden_key,
+                        &self.user_agent,
+                        &tokens.csrf_token,
+                        tokens.phpsessid.as_deref(),
+                        order_id,
+                    )
+                    .await
+            })
+            .await?;
+        if let Some(error) = res.error {
+            return Err(FunPayError::RefundFailed(res.msg.unwrap_or(error)));
+        }
+        Ok(RefundResult {
+            order_id: OrderId::from(order_id.to_string()),
+            message: res.msg,
+        })
+    }
+
+    /// Applies `params` on top of the offer's current values. The result is
+    /// validated against the live form first, so unknown select options or
+    /// missing required fields fail with `FunPayError::OfferValidation`
+    /// without reaching FunPay.
     pub async fn edit_offer(
         &self,
         offer_id: i64,
         node_id: i64,
         params: OfferEditParams,
-    ) -> Result<Value, FunPayError> {
+    ) -> Result<OfferEditResult, FunPayError> {
+        let (changes, merged) = self.prepare_offer_edit(offer_id, node_id, params).await?;
+        let save = self
+            .save_offer(offer_id, node_id, &merged)
+            .await?
+            .into_result()?;
+        Ok(OfferEditResult { changes, save })
+    }
+
+    /// Computes what `edit_offer` would change without posting anything.
+    pub async fn edit_offer_preview(
+        &self,
+        offer_id: i64,
+        node_id: i64,
+        params: OfferEditParams,
+    ) -> Result<OfferChangeSet, FunPayError> {
+        let (changes, _) = self.prepare_offer_edit(offer_id, node_id, params).await?;
+        Ok(changes)
+    }
+
+    /// Runs `edit_offer` for every `(offer_id, node_id, params)` entry with at
+    /// most `options.concurrency` offers in flight. Results are reported per
+    /// offer, in input order; one failing offer does not stop the others.
+    pub async fn bulk_edit_offers(
+        &self,
+        edits: Vec<(i64, i64, OfferEditParams)>,
+        options: BulkEditOptions,
+    ) -> Vec<BulkEditResult> {
+        let limiter = RateLimiter::new(options.min_interval);
+        let limiter = &limiter;
+        let dry_run = options.dry_run;
+
+        stream::iter(edits)
+            .map(|(offer_id, node_id, params)| async move {
+                let outcome = async {
+                    limiter.acquire().await;
+                    let (changes, merged) =
+                        self.prepare_offer_edit(offer_id, node_id, params).await?;
+                    if dry_run {
+                        return Ok(BulkEditOutcome::Preview(changes));
+                    }
+                    limiter.acquire().await;
+                    let save = self
+                        .save_offer(offer_id, node_id, &merged)
+                        .await?
+                        .into_result()?;
+                    Ok(BulkEditOutcome::Saved(OfferEditResult { changes, save }))
+                }
+                .await;
+                if let Err(e) = &outcome {
+                    log::warn!(target: "funpay_client", "Bulk edit of offer {offer_id} failed: {e}");
+                }
+                BulkEditResult {
+                    offer_id,
+                    node_id,
+                    outcome,
+                }
+            })
+            .buffered(options.concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Fetches the offer form, validates `params` against it and merges them
+    /// on top of the current values.
+    async fn prepare_offer_edit(
+        &self,
+        offer_id: i64,
+        node_id: i64,
+        params: OfferEditParams,
+    ) -> Result<(OfferChangeSet, OfferEditParams), FunPayError> {
         let html = self
             .gateway
             .get_offer_edit_page(&self.golden_key, &self.user_agent, node_id, offer_id)
             .await?;
 
+        parse_offer_full_params(&html, offer_id, node_id).validate(&params)?;
+
         let current = parse_offer_edit_params(&html);
+        let merged = current.clone().merge(params);
+        let changes = OfferChangeSet::between(&current, &merged);
         log::debug!(
             target: "funpay_client",
-            "Parsed offer {} current params: quantity={:?}, method={:?}, price={:?}",
+            "Offer {} changes:\n{}",
             offer_id,
-            current.quantity,
-            current.method,
-            current.price
-        );
-        let merged = current.merge(params);
-        log::debug!(
-            target: "funpay_client",
-            "Merged offer {} params: quantity={:?}, method={:?}, price={:?}",
-            offer_id,
-            merged.quantity,
-            merged.method,
-            merged.price
+            changes
         );
+        Ok((changes, merged))
+    }
+
+    /// Lists a new offer in `node_id`. The draft is checked against the blank
+    /// form's custom fields before anything is posted. If the new id cannot
+    /// be told, the offer still exists: the error is
+    /// `FunPayError::OfferIdUnknown` with the candidates, and a retry would
+    /// list it twice.
+    pub async fn create_offer(&self, node_id: i64, draft: OfferDraft) -> Result<i64, FunPayError> {
+        let html = self
+            .gateway
+            .get_offer_edit_page(&self.golden_key, &self.user_agent, node_id, 0)
+            .await?;
+
+        let summaries: Vec<String> = [&draft.summary_ru, &draft.summary_en]
+            .into_iter()
+            .flatten()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        let draft = draft.into_edit_params();
+        parse_offer_full_params(&html, 0, node_id).validate(&draft)?;
+        let params = parse_offer_edit_params(&html).merge(draft);
+
+        let res = self.save_offer(0, node_id, &params).await?.into_result()?;
+        if let Some(id) = res.offer_id() {
+            return Ok(id);
+        }
 
-        self.gateway
-            .post_offer_save(OfferSaveRequest {
-                golden_key: &self.golden_key,
-                user_agent: &self.user_agent,
-                phpsessid: self.phpsessid.as_deref(),
-                csrf: &self.csrf_token,
-                offer_id,
-                node_id,
-                params: &merged,
+        // FunPay may redirect to the category instead of the new offer. The
+        // listing shows category field values before the summary, so match
+        // offers whose description contains it.
+        let candidates: Vec<i64> = self
+            .get_my_offers(node_id)
+            .await?
+            .into_iter()
+            .filter(|o| {
+                summaries.is_empty() || summaries.iter().any(|s| o.description.contains(s.as_str()))
+            })
+            .map(|o| o.id)
+            .collect();
+        match candidates.as_slice() {
+            [id] => Ok(*id),
+            _ => Err(FunPayError::OfferIdUnknown { candidates }),
+        }
+    }
+
+    async fn save_offer(
+        &self,
+        offer_id: i64,
+        node_id: i64,
+        params: &OfferEditParams,
+    ) -> Result<OfferSaveResult, FunPayError> {
+        self.session
+            .call(|tokens| async move {
+                self.gateway
+                    .post_offer_save(OfferSaveRequest {
+                        golden_key: &self.golden_key,
+                        user_agent: &self.user_agent,
+                        phpsessid: tokens.phpsessid.as_deref(),
+                        csrf: &tokens.csrf_token,
+                        offer_id,
+                        node_id,
+                        params,
+                    })
+                    .await
             })
             .await
     }
@@ -505,6 +824,264 @@ impl FunPaySender {
         parse_orders_list(&body, self.seller_id)
     }
 
+    /// One page of the sales list. Pass the previous page's `continuation`
+    /// to get the next one.
+    pub async fn get_orders_page(
+        &self,
+        filter: &OrdersFilter,
+        continuation: Option<&str>,
+    ) -> Result<OrdersPage, FunPayError> {
+        let body = self
+            .gateway
+            .get_orders_trade_page(
+                &self.golden_key,
+                &self.user_agent,
+                &filter.query(),
+                continuation,
+            )
+            .await?;
+        let mut page = parse_orders_page(&body, self.seller_id, continuation.is_none())?;
+        if is_last_page(&page.orders, filter.from, |o| o.created_at) {
+            page.continuation = None;
+        }
+        page.orders.retain(|o| filter.matches(o));
+        Ok(page)
+    }
+
+    /// Walks every page of the sales list matching `filter`, newest first.
+    pub fn iter_all_orders(
+        &self,
+        filter: OrdersFilter,
+    ) -> impl Stream<Item = Result<OrderShortcut, FunPayError>> + '_ {
+        paginate(move |continuation| {
+            let filter = filter.clone();
+            async move {
+                let page = self
+                    .get_orders_page(&filter, continuation.as_deref())
+                    .await?;
+                Ok((page.orders, page.continuation))
+            }
+        })
+    }
+
+    /// Available and frozen balance per currency.
+    pub async fn get_balance(&self) -> Result<AccountBalance, FunPayError> {
+        let html = self
+            .gateway
+            .get_balance_page(&self.golden_key, &self.user_agent, None)
+            .await?;
+        parse_balance(&html)
+    }
+
+    /// What `wallet` would receive for withdrawing `amount`, after fees.
+    pub async fn preview_withdrawal(
+        &self,
+        currency: Currency,
+        amount: Decimal,
+        method: &str,
+        wallet: &str,
+    ) -> Result<Option<Decimal>, FunPayError> {
+        let res = self
+            .session
+            .call(|tokens| async move {
+                self.gateway
+                    .post_withdraw_calc(
+                        self.withdrawal_request(&tokens, currency, amount, method, wallet),
+                    )
+                    .await
+            })
+            .await?;
+        match res.error {
+            Some(error) => Err(FunPayError::WithdrawalFailed(res.msg.unwrap_or(error))),
+            None => Ok(res.amount_ext),
+        }
+    }
+
+    /// Withdraws `amount` to `wallet` through the payout `method`. Refused
+    /// with `FunPayError::WithdrawalDenied` unless `FunPayConfig` has a
+    /// [`WithdrawalGuard`] that allows it; the form and balance are re-read
+    /// and the fee is previewed before anything is posted.
+    pub async fn request_withdrawal(
+        &self,
+        currency: Currency,
+        amount: Decimal,
+        method: &str,
+        wallet: &str,
+    ) -> Result<WithdrawalResult, FunPayError> {
+        let guard = self.withdrawal_guard.as_deref().ok_or_else(|| {
+            FunPayError::WithdrawalDenied("no withdrawal guard configured".to_string())
+        })?;
+        guard.check_wallet(method, wallet)?;
+
+        let html = self
+            .gateway
+            .get_balance_page(&self.golden_key, &self.user_agent, None)
+            .await?;
+        let methods = parse_withdrawal_methods(&html);
+        if !methods.is_empty() && !methods.iter().any(|m| m == method) {
+            return Err(FunPayError::WithdrawalFailed(format!(
+                "payout method {method} is not offered"
+            )));
+        }
+        let available = parse_balance(&html)?
+            .get(currency)
+            .map(|b| b.available)
+            .unwrap_or_default();
+        if amount > available {
+            return Err(FunPayError::WithdrawalFailed(format!(
+                "{amount} {currency} exceeds the available {available}"
+            )));
+        }
+        let receive_amount = self
+            .preview_withdrawal(currency, amount, method, wallet)
+            .await?;
+
+        guard.reserve(currency, amount)?;
+        // Posted once and never resent: a resend could pay out twice.
+        let res = self
+            .session
+            .call_once(|tokens| async move {
+                self.gateway
+                    .post_withdraw(
+                        self.withdrawal_request(&tokens, currency, amount, method, wallet),
+                    )
+                    .await
+            })
+            .await;
+        let res: WithdrawalResponse = match res {
+            Ok(res) => res,
+            // Rejected before FunPay acted on it; the session is refreshed
+            // for the next call.
+            Err(e) if e.is_session_expired() => {
+                guard.release(currency, amount);
+                return Err(e);
+            }
+            Err(FunPayError::RequestFailed { status, body, url })
+                if status == StatusCode::BAD_REQUEST =>
+            {
+                match serde_json::from_str::<WithdrawalResponse>(&body) {
+                    Ok(res) if res.error.is_some() => res,
+                    _ => return Err(FunPayError::RequestFailed { status, body, url }),
+                }
+            }
+            // Transport errors and 5xx: the withdrawal may have gone through,
+            // so the reservation is kept.
+            Err(e) => return Err(e),
+        };
+        if let Some(error) = res.error {
+            guard.release(currency, amount);
+            return Err(FunPayError::WithdrawalFailed(res.msg.unwrap_or(error)));
+        }
+        log::info!(target: "funpay_client", "Requested withdrawal of {amount} {currency} via {method}");
+        Ok(WithdrawalResult {
+            currency,
+            amount,
+            method: method.to_string(),
+            wallet: wallet.to_string(),
+            receive_amount: res.amount_ext.or(receive_amount),
+            message: res.msg,
+        })
+    }
+
+    fn withdrawal_request<'a>(
+        &'a self,
+        tokens: &'a SessionTokens,
+        currency: Currency,
+        amount: Decimal,
+        method: &'a str,
+        wallet: &'a str,
+    ) -> WithdrawalRequest<'a> {
+        WithdrawalRequest {
+            golden_key: &self.golden_key,
+            user_agent: &self.user_agent,
+            phpsessid: tokens.phpsessid.as_deref(),
+            csrf: &tokens.csrf_token,
+            currency,
+            amount,
+            method,
+            wallet,
+        }
+    }
+
+    /// One page of the balance history. Pass the previous page's
+    /// `continuation` to get the next one.
+    pub async fn get_transactions_page(
+        &self,
+        filter: &TransactionsFilter,
+        continuation: Option<&str>,
+    ) -> Result<TransactionsPage, FunPayError> {
+        let html = self
+            .gateway
+            .get_balance_page(&self.golden_key, &self.user_agent, continuation)
+            .await?;
+        let mut page = parse_transactions_page(&html, continuation.is_none())?;
+        if is_last_page(&page.transactions, filter.from, |t| t.created_at) {
+            page.continuation = None;
+        }
+        page.transactions.retain(|t| filter.matches(t));
+        Ok(page)
+    }
+
+    /// Walks the whole balance history matching `filter`, newest first.
+    pub fn iter_transactions(
+        &self,
+        filter: TransactionsFilter,
+    ) -> impl Stream<Item = Result<Transaction, FunPayError>> + '_ {
+        paginate(move |continuation| {
+            let filter = filter.clone();
+            async move {
+                let page = self
+                    .get_transactions_page(&filter, continuation.as_deref())
+                    .await?;
+                Ok((page.transactions, page.continuation))
+            }
+        })
+    }
+
+    pub async fn get_user_profile(&self, user_id: i64) -> Result<UserProfile, FunPayError> {
+        let html = self
+            .gateway
+            .get_user_page(&self.golden_key, &self.user_agent, user_id)
+            .await?;
+        parse_user_profile(&html, user_id)
+    }
+
+    /// Walks a user's public reviews, newest first. The first page comes
+    /// from the profile itself, the rest from `users/reviews`.
+    pub fn iter_user_reviews(
+        &self,
+        user_id: i64,
+    ) -> impl Stream<Item = Result<Review, FunPayError>> + '_ {
+        paginate(move |continuation| async move {
+            let html = match &continuation {
+                None => {
+                    self.gateway
+                        .get_user_page(&self.golden_key, &self.user_agent, user_id)
+                        .await?
+                }
+                Some(token) => {
+                    self.gateway
+                        .post_user_reviews(&self.golden_key, &self.user_agent, user_id, token)
+                        .await?
+                }
+            };
+            let mut page = parse_user_reviews(&html);
+            if is_last_page(&page.reviews, None, |_| None) {
+                page.continuation = None;
+            }
+            Ok((page.reviews, page.continuation))
+        })
+    }
+
+    /// Orders we placed as a buyer.
+    pub async fn get_purchases(&self) -> Result<Vec<PurchaseShortcut>, FunPayError> {
+        let body = self
+            .gateway
+            .get_orders_purchases(&self.golden_key, &self.user_agent)
+            .await?;
+        parse_purchases_list(&body, self.seller_id)
+    }
+
     pub async fn get_category_subcategories(
         &self,
         node_id: i64,
@@ -527,9 +1104,920 @@ impl FunPaySender {
         Ok(parse_category_filters(&html))
     }
 
-    pub async fn calc_price(&self, node_id: i64, price: f64) -> Result<Value, FunPayError> {
-        self.gateway
+    /// Buyer prices for a seller price of `price` in `node_id`. A price the
+    /// category rejects comes back as `OfferValidation` on the `price` field.
+    pub async fn calc_price(
+        &self,
+        node_id: i64,
+        price: Decimal,
+    ) -> Result<PriceCalculation, FunPayError> {
+        let calc = self
+            .gateway
             .calc_price(&self.golden_key, &self.user_agent, node_id, price)
+            .await?;
+        match calc.error {
+            Some(error) => Err(FunPayError::OfferValidation {
+                field_errors: BTreeMap::from([(String::from("price"), error)]),
+            }),
+            None => Ok(calc),
+        }
+    }
+
+    /// Seller price to set so that buyers pay at most `buyer_price`. The
+    /// estimate is checked with another calculation and, while rounding
+    /// pushes the buyer price over the target, stepped down by at least a
+    /// kopeck and checked again.
+    pub async fn seller_price_for(
+        &self,
+        node_id: i64,
+        buyer_price: Decimal,
+    ) -> Result<Decimal, FunPayError> {
+        let no_methods =
+            || FunPayError::Parse(String::from("lots/calc returned no payment methods"));
+        let kopeck = Decimal::new(1, 2);
+        let mut price = self
+            .calc_price(node_id, buyer_price)
+            .await?
+            .seller_price_for(buyer_price)
+            .ok_or_else(no_methods)?;
+        for _ in 0..SELLER_PRICE_CHECKS {
+            if price <= Decimal::ZERO {
+                break;
+            }
+            let check = self.calc_price(node_id, price).await?;
+            if check.buyer_price().ok_or_else(no_methods)? <= buyer_price {
+                return Ok(price);
+            }
+            price = check
+                .seller_price_for(buyer_price)
+                .filter(|lower| *lower < price)
+                .unwrap_or(price - kopeck);
+        }
+        Err(FunPayError::Parse(format!(
+            "no seller price in node {node_id} keeps the buyer price at {buyer_price}"
+        )))
+    }
+
+    /// Raises the offers of `node_ids` (subcategories of `game_id`). A running
+    /// cooldown is not an error: the result then has nothing in `raised` and
+    /// `next_available_at` set.
+    pub async fn raise_lots(
+        &self,
+        game_id: i64,
+        node_ids: &[i64],
+    ) -> Result<RaiseResult, FunPayError> {
+        let Some(&node_id) = node_ids.first() else {
+            return Ok(RaiseResult {
+                game_id,
+                raised: Vec::new(),
+                next_available_at: None,
+                message: None,
+            });
+        };
+
+        let mut node_ids = node_ids.to_vec();
+        let mut res = self.post_lots_raise(game_id, node_id, &node_ids).await?;
+        if let Some(modal) = res.modal.as_deref() {
+            // FunPay asks which subcategories to raise; answer with the
+            // requested ones it offers.
+            let offered = parse_raise_modal_nodes(modal);
+            node_ids.retain(|id| offered.contains(id));
+            let Some(&node_id) = node_ids.first() else {
+                return Err(FunPayError::RaiseFailed(format!(
+                    "none of the requested subcategories are offered for raising: {offered:?}"
+                )));
+            };
+            res = self.post_lots_raise(game_id, node_id, &node_ids).await?;
+            if res.modal.is_some() {
+                return Err(FunPayError::RaiseFailed(String::from(
+                    "FunPay asked for the subcategories again",
+                )));
+            }
+        }
+
+        let message = res.msg.clone().or_else(|| res.error.clone());
+        if res.error.is_none() && res.url.is_none() {
+            return Ok(RaiseResult {
+                game_id,
+                raised: node_ids,
+                next_available_at: None,
+                message,
+            });
+        }
+        match message.as_deref().and_then(parse_wait_duration) {
+            Some(wait) => Ok(RaiseResult {
+                game_id,
+                raised: Vec::new(),
+                next_available_at: Some(funpay_now() + wait),
+                message,
+            }),
+            None => Err(FunPayError::RaiseFailed(message.unwrap_or_else(|| {
+                format!("redirected to {}", res.url.unwrap_or_default())
+            }))),
+        }
+    }
+
+    /// Lot subcategories with active offers, grouped by game id.
+    pub async fn get_raisable_categories(&self) -> Result<BTreeMap<i64, Vec<i64>>, FunPayError> {
+        let profile = self
+            .gateway
+            .get_user_page(&self.golden_key, &self.user_agent, self.seller_id)
+            .await?;
+        let mut games: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+        for node_id in parse_user_lot_nodes(&profile) {
+            let html = self
+                .gateway
+                .get_lots_trade_page(&self.golden_key, &self.user_agent, node_id)
+                .await?;
+            match parse_raise_game_id(&html) {
+                Some(game_id) => games.entry(game_id).or_default().push(node_id),
+                None => log::debug!(
+                    target: "funpay_client",
+                    "No raise button for node {node_id}, skipping"
+                ),
+            }
+        }
+        Ok(games)
+    }
+
+    async fn post_lots_raise(
+        &self,
+        game_id: i64,
+        node_id: i64,
+        node_ids: &[i64],
+    ) -> Result<RaiseResponse, FunPayError> {
+        self.session
+            .call(|tokens| async move {
+                self.gateway
+                    .post_lots_raise(RaiseRequest {
+                        golden_key: &self.golden_key,
+                        user_agent: &self.user_agent,
+                        phpsessid: tokens.phpsessid.as_deref(),
+                        csrf: &tokens.csrf_token,
+                        game_id,
+                        node_id,
+                        node_ids,
+                    })
+                    .await
+            })
             .await
     }
+
+    /// Raises every category with active offers as soon as its cooldown
+    /// ends, forever. Categories are re-discovered on every round so newly
+    /// listed ones are picked up.
+    async fn run_lots_raiser(self, events_tx: Sender<Event>, error_retry_delay: Duration) {
+        let recheck = chrono::Duration::from_std(RAISE_RECHECK_INTERVAL).unwrap_or_default();
+        let retry = chrono::Duration::from_std(error_retry_delay).unwrap_or_default();
+        let mut next_at: HashMap<i64, DateTime<FixedOffset>> = HashMap::new();
+        loop {
+            let categories = match self.get_raisable_categories().await {
+                Ok(categories) => categories,
+                Err(e) => {
+                    log::error!(target: "funpay_client", "Failed to list raisable categories: {e}");
+                    sleep(error_retry_delay).await;
+                    continue;
+                }
+            };
+            next_at.retain(|game_id, _| categories.contains_key(game_id));
+
+            for (game_id, node_ids) in categories {
+                let now = funpay_now();
+                if next_at.get(&game_id).is_some_and(|at| *at > now) {
+                    continue;
+                }
+                let next = match self.raise_lots(game_id, &node_ids).await {
+                    Ok(res) => {
+                        if !res.raised.is_empty() {
+                            log::info!(target: "funpay_client", "Raised game {game_id} nodes {:?}", res.raised);
+                            let _ = events_tx.send(Event::LotsRaised {
+                                game_id,
+                                node_ids: res.raised,
+                            });
+                        }
+                        res.next_available_at.unwrap_or(now + recheck)
+                    }
+                    Err(e) => {
+                        log::error!(target: "funpay_client", "Failed to raise game {game_id}: {e}");
+                        now + retry
+                    }
+                };
+                next_at.insert(game_id, next);
+            }
+
+            let now = funpay_now();
+            let wait = next_at
+                .values()
+                .min()
+                .map_or(RAISE_RECHECK_INTERVAL, |at| {
+                    (*at - now).to_std().unwrap_or_default()
+                })
+                .clamp(Duration::from_secs(1), RAISE_RECHECK_INTERVAL);
+            sleep(wait).await;
+        }
+    }
+
+    /// Sends the `c-p-u` runner object every `options.interval` and loads the
+    /// home page every `options.home_interval`, as an open browser tab does.
+    /// Outside the configured windows nothing is sent and the status lapses.
+    async fn run_presence(self, options: PresenceOptions, error_retry_delay: Duration) {
+        let mut tag = random_tag();
+        let mut home_due: Option<tokio::time::Instant> = None;
+        loop {
+            if !options.is_active_at(&funpay_now()) {
+                home_due = None;
+                sleep(options.interval).await;
+                continue;
+            }
+
+            let object = RunnerObject::CPUOnline(CPUOnline {
+                user_id: self.seller_id,
+                tag: tag.clone(),
+            });
+            let mut failed = match self.post_runner(&[object], None).await {
+                Ok(res) => {
+                    for obj in res.objects {
+                        if let RunnerObjectResult::CPUOnline { tag: Some(t), .. } = obj {
+                            tag = t;
+                        }
+                    }
+                    false
+                }
+                Err(e) => {
+                    log::error!(target: "funpay_client", "Failed to send online status: {e}");
+                    true
+                }
+            };
+
+            if home_due.is_none_or(|at| at <= tokio::time::Instant::now()) {
+                match Session::login(self.gateway.as_ref(), &self.golden_key, &self.user_agent)
+                    .await
+                {
+                    Ok(_) => {
+                        home_due = Some(tokio::time::Instant::now() + options.home_interval);
+                    }
+                    Err(e) => {
+                        log::error!(target: "funpay_client", "Failed to load home page: {e}");
+                        failed = true;
+                    }
+                }
+            }
+
+            sleep(if failed {
+                error_retry_delay
+            } else {
+                options.interval
+            })
+            .await;
+        }
+    }
+}
+
+/// Streams the items of a paged list. `fetch` loads the page for a
+/// continuation token (`None` for the first) and returns its items and the
+/// next token. The walk ends on a page without a token, or on one handing
+/// back the token it was loaded with, which would otherwise loop forever.
+fn paginate<'a, T, F, Fut>(fetch: F) -> impl Stream<Item = Result<T, FunPayError>> + 'a
+where
+    T: 'a,
+    F: Fn(Option<String>) -> Fut + 'a,
+    Fut: Future<Output = Result<(Vec<T>, Option<String>), FunPayError>> + 'a,
+{
+    stream::try_unfold(Some(None::<String>), move |cursor| {
+        let page = cursor.map(|continuation| (fetch(continuation.clone()), continuation));
+        async move {
+            let Some((page, continuation)) = page else {
+                return Ok::<_, FunPayError>(None);
+            };
+            let (items, next) = page.await?;
+            let next = next
+                .filter(|token| continuation.as_ref() != Some(token))
+                .map(Some);
+            Ok(Some((
+                stream::iter(items.into_iter().map(Ok::<_, FunPayError>)),
+                next,
+            )))
+        }
+    })
+    .try_flatten()
+}
+
+/// Whether a page is the last one worth loading whatever its token says:
+/// it is empty, or it reaches back past `from`, as lists are newest first.
+fn is_last_page<T>(
+    items: &[T],
+    from: Option<DateTime<FixedOffset>>,
+    created_at: impl Fn(&T) -> Option<DateTime<FixedOffset>>,
+) -> bool {
+    items.is_empty()
+        || from.is_some_and(|from| {
+            items
+                .iter()
+                .any(|item| created_at(item).is_some_and(|at| at < from))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::MockGateway;
+    use crate::models::{PaymentMethodPrice, RefundResponse};
+    use crate::runner::ChatHistoryResponse;
+
+    fn sender(gateway: &Arc<MockGateway>) -> FunPaySender {
+        let session = Session::new(
+            gateway.clone(),
+            "key".to_string(),
+            "ua".to_string(),
+            SessionTokens {
+                csrf_token: "csrf".to_string(),
+                phpsessid: Some("sess".to_string()),
+            },
+        );
+        FunPaySender {
+            gateway: gateway.clone(),
+            golden_key: "key".to_string(),
+            user_agent: "ua".to_string(),
+            session: Arc::new(session),
+            seller_id: 7,
+            withdrawal_guard: None,
+            bot_marker: false,
+        }
+    }
+
+    fn history_page(gateway: &MockGateway, ids: &[i64]) {
+        let messages: Vec<String> = ids
+            .iter()
+            .map(|id| format!(r#"{{"id":{id},"author":42,"html":"msg {id}"}}"#))
+            .collect();
+        gateway.reply_json::<ChatHistoryResponse>(
+            "get_chat_history",
+            &format!(r#"{{"chat":{{"messages":[{}]}}}}"#, messages.join(",")),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chat_history_paging() {
+        let gateway = Arc::new(MockGateway::new());
+        history_page(&gateway, &[8, 10, 9]);
+        history_page(&gateway, &[8, 7, 6]);
+        // The same cursor comes back: nothing older, so the walk ends.
+        history_page(&gateway, &[8, 7, 6]);
+        let sender = sender(&gateway);
+
+        let ids: Vec<i64> = sender
+            .get_chat_history("users-7-42", ChatHistoryOptions::new())
+            .map_ok(|m| m.id)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![10, 9, 8, 7, 6]);
+        assert_eq!(
+            gateway.calls("get_chat_history"),
+            vec![
+                "users-7-42 None",
+                "users-7-42 Some(8)",
+                "users-7-42 Some(6)"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chat_history_stop_conditions() {
+        let gateway = Arc::new(MockGateway::new());
+        history_page(&gateway, &[10, 9, 8]);
+        history_page(&gateway, &[7, 6]);
+        history_page(&gateway, &[10, 9, 8]);
+        let sender = sender(&gateway);
+
+        let options = ChatHistoryOptions::new().with_until_id(7);
+        let ids: Vec<i64> = sender
+            .get_chat_history("users-7-42", options)
+            .map_ok(|m| m.id)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![10, 9, 8]);
+        assert_eq!(gateway.count("get_chat_history"), 2);
+
+        let options = ChatHistoryOptions::new().with_limit(2);
+        let ids: Vec<i64> = sender
+            .get_chat_history("users-7-42", options)
+            .map_ok(|m| m.id)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![10, 9]);
+        assert_eq!(gateway.count("get_chat_history"), 3);
+    }
+
+    fn orders_page(first: bool, order_id: &str, continuation: &str) -> String {
+        format!(
+            r#"<html><body>{}
+            <a class="tc-item info" href="https://funpay.com/orders/{order_id}/">
+                <div class="tc-order">#{order_id}</div>
+                <div class="order-desc"><div>Gold</div><div class="text-muted">WoW, Gold</div></div>
+                <div class="tc-user"><div class="media-user-name"><span data-href="https://funpay.com/users/42/">bob</span></div></div>
+                <div class="tc-price">100 <span class="unit">₽</span></div>
+            </a>
+            <input type="hidden" name="continue" value="{continuation}">
+            </body></html>"#,
+            if first {
+                r#"<div class="user-link-name">me</div>"#
+            } else {
+                ""
+            }
+        )
+    }
+
+    #[tokio::test]
+    async fn test_iter_all_orders_stops_on_repeated_token() {
+        let gateway = Arc::new(MockGateway::new());
+        gateway.reply::<String>(
+            "get_orders_trade_page",
+            Ok(orders_page(true, "AAAA1111", "tok")),
+        );
+        gateway.reply::<String>(
+            "get_orders_trade_page",
+            Ok(orders_page(false, "BBBB2222", "tok")),
+        );
+        let sender = sender(&gateway);
+
+        let ids: Vec<String> = sender
+            .iter_all_orders(OrdersFilter::new())
+            .map_ok(|o| o.id.to_string())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(ids, vec!["AAAA1111", "BBBB2222"]);
+        assert_eq!(
+            gateway.calls("get_orders_trade_page"),
+            vec!["None", "Some(\"tok\")"]
+        );
+    }
+
+    fn transactions_page(first: bool, id: i64, continuation: &str) -> String {
+        format!(
+            r#"<html><body>{}
+            <div class="tc-item transaction-status-complete" data-transaction="{id}">
+                <span class="tc-date-time">14 марта 2024, 12:30</span>
+                <span class="tc-title">Заказ #ABCD1234</span>
+                <span class="tc-price">+ 100 ₽</span>
+            </div>
+            <input type="hidden" name="continue" value="{continuation}">
+            </body></html>"#,
+            if first {
+                r#"<div class="user-link-name">me</div>"#
+            } else {
+                ""
+            }
+        )
+    }
+
+    #[tokio::test]
+    async fn test_iter_transactions_stops_on_repeated_token() {
+        let gateway = Arc::new(MockGateway::new());
+        gateway.reply::<String>("get_balance_page", Ok(transactions_page(true, 901, "900")));
+        gateway.reply::<String>("get_balance_page", Ok(transactions_page(false, 900, "900")));
+        let sender = sender(&gateway);
+
+        let ids: Vec<i64> = sender
+            .iter_transactions(TransactionsFilter::new())
+            .map_ok(|t| t.id)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![901, 900]);
+        assert_eq!(gateway.count("get_balance_page"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_bot_marker_is_opt_in() {
+        let gateway = Arc::new(MockGateway::new());
+        for _ in 0..2 {
+            gateway.reply_json::<RunnerResponse>("post_runner", r#"{"objects":[],"response":{}}"#);
+            gateway.reply_json::<ReviewResponse>("post_review", "{}");
+        }
+        let plain = sender(&gateway);
+        let marked = sender(&gateway).with_bot_marker(true);
+
+        plain
+            .send_chat_message("users-7-42", "hello")
+            .await
+            .unwrap();
+        plain.reply_to_review("ABCD1234", "thanks").await.unwrap();
+        marked
+            .send_chat_message("users-7-42", "hello")
+            .await
+            .unwrap();
+        marked.reply_to_review("ABCD1234", "thanks").await.unwrap();
+
+        let sent = gateway.calls("post_runner");
+        assert!(sent[0].contains("content: \"hello\""));
+        assert!(sent[1].contains("content: \"\\u{2064}hello\""));
+        let replies = gateway.calls("post_review");
+        assert_eq!(replies[0], "ABCD1234 Some(\"thanks\")");
+        assert_eq!(replies[1], "ABCD1234 Some(\"\\u{2064}thanks\")");
+    }
+
+    #[tokio::test]
+    async fn test_send_chat_message_retries_after_refresh() {
+        let gateway = Arc::new(MockGateway::new());
+        gateway.reply::<RunnerResponse>("post_runner", Err(FunPayError::Unauthorized));
+        gateway.reply_home(7, "fresh", &[]);
+        gateway.reply_json::<RunnerResponse>("post_runner", r#"{"objects":[],"response":{}}"#);
+        let sender = sender(&gateway);
+
+        sender
+            .send_chat_message("users-7-42", "hello")
+            .await
+            .unwrap();
+        let sent = gateway.calls("post_runner");
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].starts_with("csrf "));
+        assert!(sent[1].starts_with("fresh "));
+    }
+
+    #[tokio::test]
+    async fn test_send_chat_image() {
+        let gateway = Arc::new(MockGateway::new());
+        gateway.reply::<String>("upload_chat_image", Ok("5501".to_string()));
+        gateway.reply_json::<RunnerResponse>("post_runner", r#"{"objects":[],"response":{}}"#);
+        gateway.reply::<String>(
+            "upload_chat_image",
+            Err(FunPayError::Parse(
+                "image upload failed: missing fileId".to_string(),
+            )),
+        );
+        let sender = sender(&gateway);
+
+        sender
+            .send_chat_image("users-7-42", vec![1, 2, 3], "shot.png")
+            .await
+            .unwrap();
+        assert_eq!(gateway.calls("upload_chat_image"), vec!["csrf shot.png"]);
+        let sent = gateway.calls("post_runner");
+        assert!(sent[0].contains("image_id: \"5501\""));
+
+        // Without an image id nothing is posted to the chat.
+        assert!(matches!(
+            sender
+                .send_chat_image("users-7-42", vec![1, 2, 3], "shot.png")
+                .await,
+            Err(FunPayError::Parse(_))
+        ));
+        assert_eq!(gateway.count("post_runner"), 1);
+    }
+
+    fn my_offers_page(offers: &[(i64, &str)]) -> String {
+        offers
+            .iter()
+            .map(|(id, desc)| {
+                format!(
+                    r#"<a href="https://funpay.com/lots/offer?id={id}" class="tc-item" data-offer="{id}">
+                    <div class="tc-server hidden-xxs">EU</div>
+                    <div class="tc-desc"><div class="tc-desc-text">{desc}</div></div>
+                    <div class="tc-amount hidden-xxs">1</div>
+                    <div class="tc-price" data-s="10"><div>10 <span class="unit">₽</span></div></div>
+                    </a>"#
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_create_offer_identifies_new_offer() {
+        let gateway = Arc::new(MockGateway::new());
+        gateway.reply::<String>("get_offer_edit_page", Ok("<form></form>".to_string()));
+        gateway.reply_json::<OfferSaveResult>(
+            "post_offer_save",
+            r#"{"done":true,"url":"https://funpay.com/lots/offer?id=800"}"#,
+        );
+        let redirect = r#"{"done":true,"url":"https://funpay.com/lots/210/trade"}"#;
+        for _ in 0..2 {
+            gateway.reply::<String>("get_offer_edit_page", Ok("<form></form>".to_string()));
+            gateway.reply_json::<OfferSaveResult>("post_offer_save", redirect);
+        }
+        gateway.reply::<String>(
+            "get_lots_trade_page",
+            Ok(my_offers_page(&[
+                (500, "Европа, Silver"),
+                (600, "Европа, Gold"),
+            ])),
+        );
+        gateway.reply::<String>(
+            "get_lots_trade_page",
+            Ok(my_offers_page(&[
+                (500, "Европа, Gold"),
+                (600, "Европа, Gold"),
+            ])),
+        );
+        let sender = sender(&gateway);
+
+        // An id in the redirect needs no listing.
+        let draft = OfferDraft::new("10").with_summary_ru("Gold");
+        assert_eq!(sender.create_offer(210, draft.clone()).await.unwrap(), 800);
+        assert_eq!(gateway.count("get_lots_trade_page"), 0);
+
+        assert_eq!(sender.create_offer(210, draft.clone()).await.unwrap(), 600);
+        match sender.create_offer(210, draft).await {
+            Err(FunPayError::OfferIdUnknown { candidates }) => {
+                assert_eq!(candidates, vec![500, 600])
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bulk_edit_collects_errors_per_offer() {
+        let gateway = Arc::new(MockGateway::new());
+        let page = || Ok::<_, FunPayError>("<form></form>".to_string());
+        gateway.reply("get_offer_edit_page", page());
+        gateway.reply::<String>(
+            "get_offer_edit_page",
+            Err(FunPayError::RequestFailed {
+                status: StatusCode::NOT_FOUND,
+                body: String::new(),
+                url: String::new(),
+            }),
+        );
+        gateway.reply("get_offer_edit_page", page());
+        gateway.reply_json::<OfferSaveResult>("post_offer_save", r#"{"done":true}"#);
+        gateway.reply_json::<OfferSaveResult>(
+            "post_offer_save",
+            r#"{"error":"Check the form","errors":{"price":"Too low"}}"#,
+        );
+        let sender = sender(&gateway);
+
+        let edits = (1..=3)
+            .map(|id| (id, 210, OfferEditParams::new().with_price("10")))
+            .collect();
+        let options = BulkEditOptions::new()
+            .with_concurrency(1)
+            .with_min_interval(Duration::from_millis(20));
+        let start = tokio::time::Instant::now();
+        let results = sender.bulk_edit_offers(edits, options).await;
+
+        // Five requests, 20ms apart on the paused clock.
+        assert_eq!(start.elapsed(), Duration::from_millis(80));
+        assert_eq!(
+            results.iter().map(|r| r.offer_id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert!(matches!(results[0].outcome, Ok(BulkEditOutcome::Saved(_))));
+        assert!(matches!(
+            results[1].outcome,
+            Err(FunPayError::RequestFailed { .. })
+        ));
+        match &results[2].outcome {
+            Err(FunPayError::OfferValidation { field_errors }) => {
+                assert_eq!(field_errors["price"], "Too low");
+            }
+            other => panic!("unexpected outcome: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raise_lots_modal() {
+        let modal = r#"{"modal":"<input type=\"checkbox\" value=\"211\"><input type=\"checkbox\" value=\"212\">"}"#;
+        let gateway = Arc::new(MockGateway::new());
+        gateway.reply_json::<RaiseResponse>("post_lots_raise", modal);
+        gateway.reply_json::<RaiseResponse>("post_lots_raise", r#"{"msg":"Raised"}"#);
+        gateway.reply_json::<RaiseResponse>("post_lots_raise", modal);
+        gateway.reply_json::<RaiseResponse>("post_lots_raise", modal);
+        gateway.reply_json::<RaiseResponse>("post_lots_raise", modal);
+        let sender = sender(&gateway);
+
+        let res = sender.raise_lots(41, &[210, 211]).await.unwrap();
+        assert_eq!(res.raised, vec![211]);
+        assert_eq!(
+            gateway.calls("post_lots_raise"),
+            vec!["41 210 [210, 211]", "41 211 [211]"]
+        );
+
+        // Still a modal after answering it.
+        assert!(matches!(
+            sender.raise_lots(41, &[211]).await,
+            Err(FunPayError::RaiseFailed(_))
+        ));
+        // None of the requested nodes offered: nothing is posted again.
+        assert!(matches!(
+            sender.raise_lots(41, &[300]).await,
+            Err(FunPayError::RaiseFailed(_))
+        ));
+        assert_eq!(gateway.count("post_lots_raise"), 5);
+    }
+
+    fn calculation(price: &str, buyer: &str) -> PriceCalculation {
+        PriceCalculation {
+            price: price.parse().unwrap(),
+            methods: vec![PaymentMethodPrice {
+                name: "Card".to_string(),
+                price: buyer.parse().unwrap(),
+                unit: "₽".to_string(),
+                sort: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_seller_price_for_steps_down_until_within_target() {
+        let gateway = Arc::new(MockGateway::new());
+        for (price, buyer) in [
+            ("100", "110"),
+            ("90.90", "100.01"),
+            ("90.89", "100.01"),
+            ("90.88", "99.99"),
+        ] {
+            gateway.reply("calc_price", Ok(calculation(price, buyer)));
+        }
+        let sender = sender(&gateway);
+
+        let price = sender
+            .seller_price_for(210, Decimal::ONE_HUNDRED)
+            .await
+            .unwrap();
+        assert_eq!(price, Decimal::new(9088, 2));
+        assert_eq!(
+            gateway.calls("calc_price"),
+            vec!["210 100", "210 90.90", "210 90.89", "210 90.88"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refund_order() {
+        let gateway = Arc::new(MockGateway::new());
+        gateway.reply_json::<RefundResponse>("post_order_refund", r#"{"msg":"Refunded"}"#);
+        gateway.reply_json::<RefundResponse>(
+            "post_order_refund",
+            r#"{"error":true,"msg":"Order is closed"}"#,
+        );
+        gateway.reply::<RefundResponse>("post_order_refund", Err(FunPayError::Unauthorized));
+        gateway.reply_home(7, "fresh", &[]);
+        let sender = sender(&gateway);
+
+        let res = sender.refund_order("#ABCD1234").await.unwrap();
+        assert_eq!(res.order_id.to_string(), "ABCD1234");
+        assert_eq!(res.message.as_deref(), Some("Refunded"));
+        assert!(matches!(
+            sender.refund_order("ABCD1234").await,
+            Err(FunPayError::RefundFailed(msg)) if msg == "Order is closed"
+        ));
+
+        // A stale session is refreshed, but the refund is not resent.
+        assert!(matches!(
+            sender.refund_order("ABCD1234").await,
+            Err(FunPayError::Unauthorized)
+        ));
+        assert_eq!(gateway.count("get_home"), 1);
+        assert_eq!(
+            gateway.calls("post_order_refund"),
+            vec!["csrf ABCD1234", "csrf ABCD1234", "csrf ABCD1234"]
+        );
+        assert_eq!(sender.session().tokens().csrf_token, "fresh");
+    }
+
+    fn reviews_page(texts: &[&str], continuation: &str) -> String {
+        let reviews: String = texts
+            .iter()
+            .map(|text| {
+                format!(
+                    r#"<div class="review-container"><div class="review-item-text">{text}</div></div>"#
+                )
+            })
+            .collect();
+        format!(
+            r#"<html><body>{reviews}<input type="hidden" name="continue" value="{continuation}"></body></html>"#
+        )
+    }
+
+    #[tokio::test]
+    async fn test_iter_user_reviews() {
+        let gateway = Arc::new(MockGateway::new());
+        gateway.reply::<String>("get_user_page", Ok(reviews_page(&["a", "b"], "t1")));
+        gateway.reply::<String>("post_user_reviews", Ok(reviews_page(&["c"], "t2")));
+        // Handing back the same token ends the walk.
+        gateway.reply::<String>("post_user_reviews", Ok(reviews_page(&["d"], "t2")));
+        gateway.reply::<String>("get_user_page", Ok(reviews_page(&["a"], "t1")));
+        // An empty page ends it too, whatever its token.
+        gateway.reply::<String>("post_user_reviews", Ok(reviews_page(&[], "t2")));
+        let sender = sender(&gateway);
+
+        let texts: Vec<String> = sender
+            .iter_user_reviews(42)
+            .map_ok(|r| r.text.unwrap_or_default())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(texts, vec!["a", "b", "c", "d"]);
+        assert_eq!(gateway.calls("post_user_reviews"), vec!["42 t1", "42 t2"]);
+
+        let count = sender
+            .iter_user_reviews(42)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap()
+            .len();
+        assert_eq!(count, 1);
+        assert_eq!(gateway.count("post_user_reviews"), 3);
+    }
+
+    const BALANCE_PAGE: &str = r#"<html><body><div class="user-link-name">me</div>
+        <select name="currency_id" data-balance-rub="5000" data-balance-total-rub="5000"></select>
+        <select name="ext_currency_id"><option value="card_rub">Card</option></select>
+        </body></html>"#;
+
+    #[tokio::test]
+    async fn test_request_withdrawal() {
+        let gateway = Arc::new(MockGateway::new());
+        let guard = Arc::new(
+            WithdrawalGuard::new()
+                .with_daily_max(Currency::Rub, Decimal::from(1000))
+                .with_allowed_wallet("card_rub", "2200"),
+        );
+        let unguarded = sender(&gateway);
+        let sender = FunPaySender {
+            withdrawal_guard: Some(guard.clone()),
+            ..sender(&gateway)
+        };
+        let amount = Decimal::from(400);
+        let withdraw = || sender.request_withdrawal(Currency::Rub, amount, "card_rub", "2200");
+
+        assert!(matches!(
+            unguarded
+                .request_withdrawal(Currency::Rub, amount, "card_rub", "2200")
+                .await,
+            Err(FunPayError::WithdrawalDenied(_))
+        ));
+        assert!(matches!(
+            sender
+                .request_withdrawal(Currency::Rub, amount, "card_rub", "4100")
+                .await,
+            Err(FunPayError::WithdrawalDenied(_))
+        ));
+        assert_eq!(gateway.count("get_balance_page"), 0);
+
+        for _ in 0..6 {
+            gateway.reply::<String>("get_balance_page", Ok(BALANCE_PAGE.to_string()));
+            gateway
+                .reply_json::<WithdrawalResponse>("post_withdraw_calc", r#"{"amount_ext":"380"}"#);
+        }
+        gateway.reply_json::<WithdrawalResponse>("post_withdraw", r#"{"msg":"Requested"}"#);
+        gateway.reply_json::<WithdrawalResponse>(
+            "post_withdraw",
+            r#"{"error":true,"msg":"Try later"}"#,
+        );
+        gateway.reply::<WithdrawalResponse>(
+            "post_withdraw",
+            Err(FunPayError::RequestFailed {
+                status: StatusCode::BAD_REQUEST,
+                body: r#"{"error":true,"msg":"Wrong wallet"}"#.to_string(),
+                url: String::new(),
+            }),
+        );
+        gateway.reply::<WithdrawalResponse>("post_withdraw", Err(FunPayError::Unauthorized));
+        gateway.reply_home(7, "fresh", &[]);
+        gateway.reply::<WithdrawalResponse>(
+            "post_withdraw",
+            Err(FunPayError::RequestFailed {
+                status: StatusCode::BAD_GATEWAY,
+                body: String::new(),
+                url: String::new(),
+            }),
+        );
+
+        let res = withdraw().await.unwrap();
+        assert_eq!(res.receive_amount, Some(Decimal::from(380)));
+        assert_eq!(guard.remaining_today(Currency::Rub), Decimal::from(600));
+
+        // Refused in the response body or a 400: the reservation is given back.
+        assert!(matches!(
+            withdraw().await,
+            Err(FunPayError::WithdrawalFailed(msg)) if msg == "Try later"
+        ));
+        assert!(matches!(
+            withdraw().await,
+            Err(FunPayError::WithdrawalFailed(msg)) if msg == "Wrong wallet"
+        ));
+        assert_eq!(guard.remaining_today(Currency::Rub), Decimal::from(600));
+
+        // A stale session: given back and refreshed, but not resent.
+        assert!(matches!(withdraw().await, Err(FunPayError::Unauthorized)));
+        assert_eq!(guard.remaining_today(Currency::Rub), Decimal::from(600));
+        assert_eq!(gateway.count("get_home"), 1);
+        assert_eq!(gateway.count("post_withdraw"), 4);
+
+        // Unknown outcome: the reservation is kept and nothing is resent.
+        assert!(withdraw().await.is_err());
+        assert_eq!(guard.remaining_today(Currency::Rub), Decimal::from(200));
+        assert_eq!(gateway.calls("post_withdraw").last().unwrap(), "fresh 400");
+
+        // Over the daily maximum: refused after the preview, never posted.
+        assert!(matches!(
+            withdraw().await,
+            Err(FunPayError::WithdrawalDenied(_))
+        ));
+        assert_eq!(gateway.count("post_withdraw"), 5);
+    }
 }
@@ -2,14 +2,46 @@ use crate::client::urls::UrlBuilder;
 use crate::client::FunpayGateway;
 use crate::config::FunPayConfig;
 use crate::error::FunPayError;
-use crate::models::OfferSaveRequest;
+use crate::models::{
+    OfferSaveRequest, OfferSaveResult, PriceCalculation, RaiseRequest, RaiseResponse,
+    RefundResponse, ReviewRequest, ReviewResponse, WithdrawalRequest, WithdrawalResponse,
+};
+use crate::runner::{ChatHistoryResponse, RunnerAction, RunnerObject, RunnerResponse};
 use async_trait::async_trait;
+use reqwest::multipart::{Form, Part};
 use reqwest::{header, redirect::Policy, Client, StatusCode};
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
 use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use rust_decimal::Decimal;
 use serde_json::Value;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Form fields `post_offer_save` always writes from `OfferEditParams`' own fields.
+const FIXED_OFFER_FORM_FIELDS: &[&str] = &[
+    "csrf_token",
+    "form_created_at",
+    "offer_id",
+    "node_id",
+    "location",
+    "deleted",
+    "fields[quantity]",
+    "fields[quantity2]",
+    "fields[method]",
+    "fields[type]",
+    "server_id",
+    "fields[desc][ru]",
+    "fields[desc][en]",
+    "fields[payment_msg][ru]",
+    "fields[payment_msg][en]",
+    "fields[summary][ru]",
+    "fields[summary][en]",
+    "fields[game]",
+    "fields[images]",
+    "price",
+    "deactivate_after_sale",
+    "active",
+];
+
 pub struct ReqwestGateway {
     pub client: ClientWithMiddleware,
     pub urls: UrlBuilder,
@@ -106,7 +138,8 @@ impl ReqwestGateway {
         builder: reqwest_middleware::RequestBuilder,
     ) -> Result<reqwest::Response, FunPayError> {
         let resp = builder.send().await?;
-        if resp.status() == StatusCode::FORBIDDEN {
+        if resp.status() == StatusCode::FORBIDDEN || resp.url().path().starts_with("/account/login")
+        {
             return Err(FunPayError::Unauthorized);
         }
         if !resp.status().is_success() {
@@ -117,6 +150,48 @@ impl ReqwestGateway {
         }
         Ok(resp)
     }
+
+    /// `withdraw/calc` and `withdraw/withdraw` take the same form.
+    async fn post_withdrawal_form(
+        &self,
+        url: &str,
+        request: WithdrawalRequest<'_>,
+    ) -> Result<WithdrawalResponse, FunPayError> {
+        let payload = format!(
+            "csrf_token={}&currency_id={}&ext_currency_id={}&wallet={}&amount_int={}",
+            urlencoding::encode(request.csrf),
+            request.currency.code().to_lowercase(),
+            urlencoding::encode(request.method),
+            urlencoding::encode(request.wallet),
+            request.amount
+        );
+        let req = self
+            .client
+            .post(url)
+            .header(
+                header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded; charset=UTF-8",
+            )
+            .header("x-requested-with", "XMLHttpRequest")
+            .header(
+                header::ACCEPT,
+                "application/json, text/javascript, */*; q=0.01",
+            )
+            .header(header::ORIGIN, self.urls.base_url())
+            .header(header::REFERER, self.urls.account_balance())
+            .body(payload);
+
+        let req = self.add_common_headers(
+            req,
+            request.golden_key,
+            request.user_agent,
+            request.phpsessid,
+        );
+        let resp = self.execute(req).await?;
+        let body = resp.text().await?;
+        serde_json::from_str(&body)
+            .map_err(|e| FunPayError::Parse(format!("withdraw response: {e}: {body}")))
+    }
 }
 
 impl Default for ReqwestGateway {
@@ -168,6 +243,29 @@ impl FunpayGateway for ReqwestGateway {
         Ok((body, set_cookies))
     }
 
+    async fn get_chat_history(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        chat_id: &str,
+        last_message: Option<i64>,
+    ) -> Result<ChatHistoryResponse, FunPayError> {
+        let url = self.urls.chat_history(chat_id, last_message);
+        let req = self
+            .client
+            .get(&url)
+            .header(
+                header::ACCEPT,
+                "application/json, text/javascript, */*; q=0.01",
+            )
+            .header("x-requested-with", "XMLHttpRequest");
+        let req = self.add_common_headers(req, golden_key, user_agent, None);
+        let resp = self.execute(req).await?;
+        let body = resp.text().await?;
+        serde_json::from_str(&body)
+            .map_err(|e| FunPayError::Parse(format!("invalid chat history response: {e}")))
+    }
+
     async fn get_orders_trade(
         &self,
         golden_key: &str,
@@ -181,6 +279,92 @@ impl FunpayGateway for ReqwestGateway {
         Ok(body)
     }
 
+    async fn get_orders_trade_page(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        query: &[(&str, String)],
+        continuation: Option<&str>,
+    ) -> Result<String, FunPayError> {
+        let url = self.urls.orders_trade_filtered(query);
+        let req = match continuation {
+            Some(token) => self
+                .client
+                .post(&url)
+                .header(
+                    header::CONTENT_TYPE,
+                    "application/x-www-form-urlencoded; charset=UTF-8",
+                )
+                .header("x-requested-with", "XMLHttpRequest")
+                .header(header::ACCEPT, "*/*")
+                .header(header::ORIGIN, self.urls.base_url())
+                .header(header::REFERER, url.as_str())
+                .body(format!("continue={}", urlencoding::encode(token))),
+            None => self.client.get(&url).header(header::ACCEPT, "*/*"),
+        };
+        let req = self.add_common_headers(req, golden_key, user_agent, None);
+        let resp = self.execute(req).await?;
+        let body = resp.text().await?;
+        Ok(body)
+    }
+
+    async fn get_balance_page(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        continuation: Option<&str>,
+    ) -> Result<String, FunPayError> {
+        let url = self.urls.account_balance();
+        let req = match continuation {
+            Some(token) => self
+                .client
+                .post(&url)
+                .header(
+                    header::CONTENT_TYPE,
+                    "application/x-www-form-urlencoded; charset=UTF-8",
+                )
+                .header("x-requested-with", "XMLHttpRequest")
+                .header(header::ACCEPT, "*/*")
+                .header(header::ORIGIN, self.urls.base_url())
+                .header(header::REFERER, url.as_str())
+                .body(format!("continue={}", urlencoding::encode(token))),
+            None => self.client.get(&url).header(header::ACCEPT, "*/*"),
+        };
+        let req = self.add_common_headers(req, golden_key, user_agent, None);
+        let resp = self.execute(req).await?;
+        let body = resp.text().await?;
+        Ok(body)
+    }
+
+    async fn post_withdraw_calc(
+        &self,
+        request: WithdrawalRequest<'_>,
+    ) -> Result<WithdrawalResponse, FunPayError> {
+        let url = self.urls.withdraw_calc();
+        self.post_withdrawal_form(&url, request).await
+    }
+
+    async fn post_withdraw(
+        &self,
+        request: WithdrawalRequest<'_>,
+    ) -> Result<WithdrawalResponse, FunPayError> {
+        let url = self.urls.withdraw();
+        self.post_withdrawal_form(&url, request).await
+    }
+
+    async fn get_orders_purchases(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+    ) -> Result<String, FunPayError> {
+        let url = self.urls.orders_purchases();
+        let req = self.client.get(&url).header(header::ACCEPT, "*/*");
+        let req = self.add_common_headers(req, golden_key, user_agent, None);
+        let resp = self.execute(req).await?;
+        let body = resp.text().await?;
+        Ok(body)
+    }
+
     async fn get_order_page(
         &self,
         golden_key: &str,
@@ -195,26 +379,113 @@ impl FunpayGateway for ReqwestGateway {
         Ok(body)
     }
 
+    async fn post_order_refund(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        csrf: &str,
+        phpsessid: Option<&str>,
+        order_id: &str,
+    ) -> Result<RefundResponse, FunPayError> {
+        let url = self.urls.order_refund();
+        let payload = format!(
+            "id={}&csrf_token={}",
+            urlencoding::encode(order_id),
+            urlencoding::encode(csrf)
+        );
+
+        let req = self
+            .client
+            .post(&url)
+            .header(
+                header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded; charset=UTF-8",
+            )
+            .header("x-requested-with", "XMLHttpRequest")
+            .header(
+                header::ACCEPT,
+                "application/json, text/javascript, */*; q=0.01",
+            )
+            .header(header::ORIGIN, self.urls.base_url())
+            .header(header::REFERER, self.urls.order_page(order_id))
+            .body(payload);
+
+        let req = self.add_common_headers(req, golden_key, user_agent, phpsessid);
+        let resp = self.execute(req).await?;
+        let body = resp.text().await?;
+        serde_json::from_str(&body)
+            .map_err(|e| FunPayError::Parse(format!("orders/refund response: {e}: {body}")))
+    }
+
+    async fn post_review(&self, request: ReviewRequest<'_>) -> Result<ReviewResponse, FunPayError> {
+        let mut form_parts = vec![
+            format!("authorId={}", request.author_id),
+            format!("orderId={}", urlencoding::encode(request.order_id)),
+            format!("csrf_token={}", urlencoding::encode(request.csrf)),
+        ];
+        let url = match request.text {
+            Some(text) => {
+                form_parts.push(format!("text={}", urlencoding::encode(text)));
+                form_parts.push("rating=".to_string());
+                self.urls.order_review()
+            }
+            None => self.urls.order_review_delete(),
+        };
+
+        let req = self
+            .client
+            .post(&url)
+            .header(
+                header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded; charset=UTF-8",
+            )
+            .header("x-requested-with", "XMLHttpRequest")
+            .header(
+                header::ACCEPT,
+                "application/json, text/javascript, */*; q=0.01",
+            )
+            .header(header::ORIGIN, self.urls.base_url())
+            .header(header::REFERER, self.urls.order_page(request.order_id))
+            .body(form_parts.join("&"));
+
+        let req = self.add_common_headers(
+            req,
+            request.golden_key,
+            request.user_agent,
+            request.phpsessid,
+        );
+        let resp = self.execute(req).await?;
+        let body = resp.text().await?;
+        serde_json::from_str(&body)
+            .map_err(|e| FunPayError::Parse(format!("orders/review response: {e}: {body}")))
+    }
+
     async fn post_runner(
         &self,
         golden_key: &str,
         user_agent: &str,
         csrf: &str,
         phpsessid: Option<&str>,
-        objects_json: &str,
-        request_json: Option<&str>,
-    ) -> Result<Value, FunPayError> {
+        objects: &[RunnerObject],
+        action: Option<&RunnerAction>,
+    ) -> Result<RunnerResponse, FunPayError> {
         let url = self.urls.runner();
-        let payload = match request_json {
-            Some(req) => format!(
-                "objects={}&request={}&csrf_token={}",
-                urlencoding::encode(objects_json),
-                urlencoding::encode(req),
-                urlencoding::encode(csrf)
-            ),
+        let objects_json =
+            serde_json::to_string(objects).map_err(|e| FunPayError::Parse(e.to_string()))?;
+        let payload = match action {
+            Some(action) => {
+                let request_json =
+                    serde_json::to_string(action).map_err(|e| FunPayError::Parse(e.to_string()))?;
+                format!(
+                    "objects={}&request={}&csrf_token={}",
+                    urlencoding::encode(&objects_json),
+                    urlencoding::encode(&request_json),
+                    urlencoding::encode(csrf)
+                )
+            }
             None => format!(
                 "objects={}&request=false&csrf_token={}",
-                urlencoding::encode(objects_json),
+                urlencoding::encode(&objects_json),
                 urlencoding::encode(csrf)
             ),
         };
@@ -232,13 +503,49 @@ impl FunpayGateway for ReqwestGateway {
             .header(header::REFERER, format!("{}/chat/", self.urls.base_url()))
             .body(payload);
 
+        let req = self.add_common_headers(req, golden_key, user_agent, phpsessid);
+        let resp = self.execute(req).await?;
+        let body = resp.text().await?;
+        serde_json::from_str(&body)
+            .map_err(|e| FunPayError::Parse(format!("invalid runner response: {e}")))
+    }
+
+    async fn upload_chat_image(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        csrf: &str,
+        phpsessid: Option<&str>,
+        image: Vec<u8>,
+        filename: &str,
+    ) -> Result<String, FunPayError> {
+        let url = self.urls.chat_image_upload();
+        let form = Form::new()
+            .part("file", Part::bytes(image).file_name(filename.to_string()))
+            .text("csrf_token", csrf.to_string());
+
+        let req = self
+            .client
+            .post(&url)
+            .header("x-requested-with", "XMLHttpRequest")
+            .header(
+                header::ACCEPT,
+                "application/json, text/javascript, */*; q=0.01",
+            )
+            .header(header::ORIGIN, self.urls.base_url())
+            .header(header::REFERER, format!("{}/chat/", self.urls.base_url()))
+            .multipart(form);
+
         let req = self.add_common_headers(req, golden_key, user_agent, phpsessid);
         let resp = self.execute(req).await?;
         let v: Value = resp.json().await?;
-        Ok(v)
+        upload_file_id(&v)
     }
 
-    async fn post_offer_save(&self, request: OfferSaveRequest<'_>) -> Result<Value, FunPayError> {
+    async fn post_offer_save(
+        &self,
+        request: OfferSaveRequest<'_>,
+    ) -> Result<OfferSaveResult, FunPayError> {
         let url = self.urls.offer_save();
         let form_created_at = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -289,6 +596,12 @@ impl FunpayGateway for ReqwestGateway {
             field("price", request.params.price.as_deref()),
         ];
 
+        for (name, value) in &request.params.extra_fields {
+            if !FIXED_OFFER_FORM_FIELDS.contains(&name.as_str()) {
+                form_parts.push(field(name, Some(value)));
+            }
+        }
+
         if request.params.deactivate_after_sale.unwrap_or(false) {
             form_parts.push(field("deactivate_after_sale[]", None));
             form_parts.push(field("deactivate_after_sale[]", Some("on")));
@@ -350,8 +663,8 @@ impl FunpayGateway for ReqwestGateway {
         );
         // execute already checks status.
 
-        let v: Value = serde_json::from_str(&body_text).unwrap_or(Value::Null);
-        Ok(v)
+        serde_json::from_str(&body_text)
+            .map_err(|e| FunPayError::Parse(format!("offerSave response: {e}: {body_text}")))
     }
 
     async fn get_offer_edit_page(
@@ -397,15 +710,101 @@ impl FunpayGateway for ReqwestGateway {
         Ok(body)
     }
 
+    async fn get_user_page(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        user_id: i64,
+    ) -> Result<String, FunPayError> {
+        let url = self.urls.user_page(user_id);
+        let req = self.client.get(&url).header(header::ACCEPT, "*/*");
+        let req = self.add_common_headers(req, golden_key, user_agent, None);
+        let resp = self.execute(req).await?;
+        let body = resp.text().await?;
+        Ok(body)
+    }
+
+    async fn post_user_reviews(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        user_id: i64,
+        continuation: &str,
+    ) -> Result<String, FunPayError> {
+        let url = self.urls.user_reviews();
+        let payload = format!(
+            "user_id={user_id}&continue={}&filter=",
+            urlencoding::encode(continuation)
+        );
+        let req = self
+            .client
+            .post(&url)
+            .header(
+                header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded; charset=UTF-8",
+            )
+            .header("x-requested-with", "XMLHttpRequest")
+            .header(header::ACCEPT, "*/*")
+            .header(header::ORIGIN, self.urls.base_url())
+            .header(header::REFERER, self.urls.user_page(user_id))
+            .body(payload);
+        let req = self.add_common_headers(req, golden_key, user_agent, None);
+        let resp = self.execute(req).await?;
+        let body = resp.text().await?;
+        Ok(body)
+    }
+
+    async fn post_lots_raise(
+        &self,
+        request: RaiseRequest<'_>,
+    ) -> Result<RaiseResponse, FunPayError> {
+        let url = self.urls.lots_raise();
+        let mut form_parts = vec![
+            format!("csrf_token={}", urlencoding::encode(request.csrf)),
+            format!("game_id={}", request.game_id),
+            format!("node_id={}", request.node_id),
+        ];
+        for id in request.node_ids {
+            form_parts.push(format!("{}={id}", urlencoding::encode("node_ids[]")));
+        }
+
+        let req = self
+            .client
+            .post(&url)
+            .header(
+                header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded; charset=UTF-8",
+            )
+            .header("x-requested-with", "XMLHttpRequest")
+            .header(
+                header::ACCEPT,
+                "application/json, text/javascript, */*; q=0.01",
+            )
+            .header(header::ORIGIN, self.urls.base_url())
+            .header(header::REFERER, self.urls.lots_trade(request.node_id))
+            .body(form_parts.join("&"));
+
+        let req = self.add_common_headers(
+            req,
+            request.golden_key,
+            request.user_agent,
+            request.phpsessid,
+        );
+        let resp = self.execute(req).await?;
+        let body = resp.text().await?;
+        serde_json::from_str(&body)
+            .map_err(|e| FunPayError::Parse(format!("lots/raise response: {e}: {body}")))
+    }
+
     async fn calc_price(
         &self,
         golden_key: &str,
         user_agent: &str,
         node_id: i64,
-        price: f64,
-    ) -> Result<Value, FunPayError> {
+        price: Decimal,
+    ) -> Result<PriceCalculation, FunPayError> {
         let url = self.urls.lots_calc();
-        let payload = format!("nodeId={}&price={}", node_id, price as i64);
+        let payload = format!("nodeId={node_id}&price={price}");
 
         let req = self
             .client
@@ -424,7 +823,42 @@ impl FunpayGateway for ReqwestGateway {
 
         let req = self.add_common_headers(req, golden_key, user_agent, None);
         let resp = self.execute(req).await?;
-        let v: Value = resp.json().await?;
-        Ok(v)
+        let body = resp.text().await?;
+        let mut calc: PriceCalculation = serde_json::from_str(&body)
+            .map_err(|e| FunPayError::Parse(format!("lots/calc response: {e}: {body}")))?;
+        calc.price = price;
+        Ok(calc)
+    }
+}
+
+/// The id of an uploaded chat image; FunPay sends it as a string or a number.
+fn upload_file_id(v: &Value) -> Result<String, FunPayError> {
+    match v.get("fileId") {
+        Some(Value::String(id)) => Ok(id.clone()),
+        Some(Value::Number(id)) => Ok(id.to_string()),
+        _ => {
+            let msg = v
+                .get("msg")
+                .and_then(|x| x.as_str())
+                .unwrap_or("missing fileId");
+            Err(FunPayError::Parse(format!("image upload failed: {msg}")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_upload_file_id() {
+        assert_eq!(upload_file_id(&json!({"fileId": "abc"})).unwrap(), "abc");
+        assert_eq!(upload_file_id(&json!({"fileId": 123})).unwrap(), "123");
+        match upload_file_id(&json!({"error": true, "msg": "Too large"})) {
+            Err(FunPayError::Parse(msg)) => assert!(msg.contains("Too large")),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        assert!(upload_file_id(&json!({})).is_err());
     }
 }
@@ -0,0 +1,305 @@
+use crate::client::FunpayGateway;
+use crate::error::FunPayError;
+use crate::models::{
+    OfferSaveRequest, OfferSaveResult, PriceCalculation, RaiseRequest, RaiseResponse,
+    RefundResponse, ReviewRequest, ReviewResponse, WithdrawalRequest, WithdrawalResponse,
+};
+use crate::runner::{ChatHistoryResponse, RunnerAction, RunnerObject, RunnerResponse};
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+type Reply = Box<dyn Any + Send>;
+
+/// Scripted gateway for tests. Replies are queued per method name and handed
+/// out in order; a call without a queued reply panics. Every call is logged
+/// with a short description of its arguments.
+#[derive(Default)]
+pub(crate) struct MockGateway {
+    replies: Mutex<HashMap<&'static str, VecDeque<Reply>>>,
+    calls: Mutex<Vec<(&'static str, String)>>,
+}
+
+impl MockGateway {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reply<T: Send + 'static>(&self, method: &'static str, reply: Result<T, FunPayError>) {
+        self.replies
+            .lock()
+            .unwrap()
+            .entry(method)
+            .or_default()
+            .push_back(Box::new(reply));
+    }
+
+    /// Queues a reply deserialized from a JSON body.
+    pub fn reply_json<T>(&self, method: &'static str, json: &str)
+    where
+        T: serde::de::DeserializeOwned + Send + 'static,
+    {
+        self.reply::<T>(method, Ok(serde_json::from_str(json).unwrap()));
+    }
+
+    /// Queues a logged-in home page for `get_home`.
+    pub fn reply_home(&self, user_id: i64, csrf: &str, set_cookies: &[&str]) {
+        let cookies = set_cookies.iter().map(|c| c.to_string()).collect();
+        self.reply::<(String, Vec<String>)>("get_home", Ok((home_page(user_id, csrf), cookies)));
+    }
+
+    /// Argument descriptions of every call to `method`, in order.
+    pub fn calls(&self, method: &str) -> Vec<String> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(m, _)| *m == method)
+            .map(|(_, args)| args.clone())
+            .collect()
+    }
+
+    pub fn count(&self, method: &str) -> usize {
+        self.calls(method).len()
+    }
+
+    fn next<T: 'static>(&self, method: &'static str, args: String) -> Result<T, FunPayError> {
+        self.calls.lock().unwrap().push((method, args));
+        let reply = self
+            .replies
+            .lock()
+            .unwrap()
+            .get_mut(method)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or_else(|| panic!("unexpected call to {method}"));
+        *reply
+            .downcast::<Result<T, FunPayError>>()
+            .unwrap_or_else(|_| panic!("wrong reply type queued for {method}"))
+    }
+}
+
+/// Home page body accepted by `Session::login`.
+fn home_page(user_id: i64, csrf: &str) -> String {
+    format!(
+        r#"<html><body data-app-data='{{"userId":{user_id},"csrf-token":"{csrf}","locale":"ru"}}'>
+        <div class="user-link-name">seller</div></body></html>"#
+    )
+}
+
+#[async_trait]
+impl FunpayGateway for MockGateway {
+    async fn get_home(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+    ) -> Result<(String, Vec<String>), FunPayError> {
+        self.next("get_home", String::new())
+    }
+
+    async fn get_chat_page(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        _chat_id: &str,
+    ) -> Result<(String, Vec<String>), FunPayError> {
+        unimplemented!()
+    }
+
+    async fn get_chat_history(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        chat_id: &str,
+        last_message: Option<i64>,
+    ) -> Result<ChatHistoryResponse, FunPayError> {
+        self.next("get_chat_history", format!("{chat_id} {last_message:?}"))
+    }
+
+    async fn get_orders_trade(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+    ) -> Result<String, FunPayError> {
+        unimplemented!()
+    }
+
+    async fn get_orders_trade_page(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        _query: &[(&str, String)],
+        continuation: Option<&str>,
+    ) -> Result<String, FunPayError> {
+        self.next("get_orders_trade_page", format!("{continuation:?}"))
+    }
+
+    async fn get_balance_page(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        continuation: Option<&str>,
+    ) -> Result<String, FunPayError> {
+        self.next("get_balance_page", format!("{continuation:?}"))
+    }
+
+    async fn post_withdraw_calc(
+        &self,
+        request: WithdrawalRequest<'_>,
+    ) -> Result<WithdrawalResponse, FunPayError> {
+        self.next(
+            "post_withdraw_calc",
+            format!("{} {}", request.csrf, request.amount),
+        )
+    }
+
+    async fn post_withdraw(
+        &self,
+        request: WithdrawalRequest<'_>,
+    ) -> Result<WithdrawalResponse, FunPayError> {
+        self.next(
+            "post_withdraw",
+            format!("{} {}", request.csrf, request.amount),
+        )
+    }
+
+    async fn get_orders_purchases(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+    ) -> Result<String, FunPayError> {
+        self.next("get_orders_purchases", String::new())
+    }
+
+    async fn get_order_page(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        _order_id: &str,
+    ) -> Result<String, FunPayError> {
+        unimplemented!()
+    }
+
+    async fn post_order_refund(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        csrf: &str,
+        _phpsessid: Option<&str>,
+        order_id: &str,
+    ) -> Result<RefundResponse, FunPayError> {
+        self.next("post_order_refund", format!("{csrf} {order_id}"))
+    }
+
+    async fn post_review(&self, request: ReviewRequest<'_>) -> Result<ReviewResponse, FunPayError> {
+        self.next(
+            "post_review",
+            format!("{} {:?}", request.order_id, request.text),
+        )
+    }
+
+    async fn post_runner(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        csrf: &str,
+        _phpsessid: Option<&str>,
+        objects: &[RunnerObject],
+        action: Option<&RunnerAction>,
+    ) -> Result<RunnerResponse, FunPayError> {
+        self.next("post_runner", format!("{csrf} {objects:?} {action:?}"))
+    }
+
+    async fn upload_chat_image(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        csrf: &str,
+        _phpsessid: Option<&str>,
+        _image: Vec<u8>,
+        filename: &str,
+    ) -> Result<String, FunPayError> {
+        self.next("upload_chat_image", format!("{csrf} {filename}"))
+    }
+
+    async fn post_offer_save(
+        &self,
+        request: OfferSaveRequest<'_>,
+    ) -> Result<OfferSaveResult, FunPayError> {
+        self.next(
+            "post_offer_save",
+            format!("{} {} {}", request.csrf, request.offer_id, request.node_id),
+        )
+    }
+
+    async fn get_offer_edit_page(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        node_id: i64,
+        offer_id: i64,
+    ) -> Result<String, FunPayError> {
+        self.next("get_offer_edit_page", format!("{node_id} {offer_id}"))
+    }
+
+    async fn get_lots_trade_page(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        node_id: i64,
+    ) -> Result<String, FunPayError> {
+        self.next("get_lots_trade_page", node_id.to_string())
+    }
+
+    async fn get_lots_page(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        _node_id: i64,
+    ) -> Result<String, FunPayError> {
+        unimplemented!()
+    }
+
+    async fn get_user_page(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        user_id: i64,
+    ) -> Result<String, FunPayError> {
+        self.next("get_user_page", user_id.to_string())
+    }
+
+    async fn post_user_reviews(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        user_id: i64,
+        continuation: &str,
+    ) -> Result<String, FunPayError> {
+        self.next("post_user_reviews", format!("{user_id} {continuation}"))
+    }
+
+    async fn post_lots_raise(
+        &self,
+        request: RaiseRequest<'_>,
+    ) -> Result<RaiseResponse, FunPayError> {
+        self.next(
+            "post_lots_raise",
+            format!(
+                "{} {} {:?}",
+                request.game_id, request.node_id, request.node_ids
+            ),
+        )
+    }
+
+    async fn calc_price(
+        &self,
+        _golden_key: &str,
+        _user_agent: &str,
+        node_id: i64,
+        price: Decimal,
+    ) -> Result<PriceCalculation, FunPayError> {
+        self.next("calc_price", format!("{node_id} {price}"))
+    }
+}
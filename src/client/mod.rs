@@ -1,7 +1,11 @@
 use crate::error::FunPayError;
-use crate::models::OfferSaveRequest;
+use crate::models::{
+    OfferSaveRequest, OfferSaveResult, PriceCalculation, RaiseRequest, RaiseResponse,
+    RefundResponse, ReviewRequest, ReviewResponse, WithdrawalRequest, WithdrawalResponse,
+};
+use crate::runner::{ChatHistoryResponse, RunnerAction, RunnerObject, RunnerResponse};
 use async_trait::async_trait;
-use serde_json::Value;
+use rust_decimal::Decimal;
 
 #[async_trait]
 pub trait FunpayGateway: Send + Sync {
@@ -16,27 +20,88 @@ pub trait FunpayGateway: Send + Sync {
         user_agent: &str,
         chat_id: &str,
     ) -> Result<(String, Vec<String>), FunPayError>;
+    async fn get_chat_history(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        chat_id: &str,
+        last_message: Option<i64>,
+    ) -> Result<ChatHistoryResponse, FunPayError>;
     async fn get_orders_trade(
         &self,
         golden_key: &str,
         user_agent: &str,
     ) -> Result<String, FunPayError>;
+    /// A page of the sales list: a GET for the first page, then a POST with
+    /// FunPay's `continue` token for the following ones.
+    async fn get_orders_trade_page(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        query: &[(&str, String)],
+        continuation: Option<&str>,
+    ) -> Result<String, FunPayError>;
+    /// The balance page, or with a `continue` token the next page of its
+    /// history.
+    async fn get_balance_page(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        continuation: Option<&str>,
+    ) -> Result<String, FunPayError>;
+    /// Fee preview for a withdrawal; nothing is withdrawn.
+    async fn post_withdraw_calc(
+        &self,
+        request: WithdrawalRequest<'_>,
+    ) -> Result<WithdrawalResponse, FunPayError>;
+    async fn post_withdraw(
+        &self,
+        request: WithdrawalRequest<'_>,
+    ) -> Result<WithdrawalResponse, FunPayError>;
+    async fn get_orders_purchases(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+    ) -> Result<String, FunPayError>;
     async fn get_order_page(
         &self,
         golden_key: &str,
         user_agent: &str,
         order_id: &str,
     ) -> Result<String, FunPayError>;
+    async fn post_order_refund(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        csrf: &str,
+        phpsessid: Option<&str>,
+        order_id: &str,
+    ) -> Result<RefundResponse, FunPayError>;
+    /// Posts a reply to a buyer's review, or deletes ours when
+    /// `request.text` is `None`.
+    async fn post_review(&self, request: ReviewRequest<'_>) -> Result<ReviewResponse, FunPayError>;
     async fn post_runner(
         &self,
         golden_key: &str,
         user_agent: &str,
         csrf: &str,
         phpsessid: Option<&str>,
-        objects_json: &str,
-        request_json: Option<&str>,
-    ) -> Result<Value, FunPayError>;
-    async fn post_offer_save(&self, request: OfferSaveRequest<'_>) -> Result<Value, FunPayError>;
+        objects: &[RunnerObject],
+        action: Option<&RunnerAction>,
+    ) -> Result<RunnerResponse, FunPayError>;
+    async fn upload_chat_image(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        csrf: &str,
+        phpsessid: Option<&str>,
+        image: Vec<u8>,
+        filename: &str,
+    ) -> Result<String, FunPayError>;
+    async fn post_offer_save(
+        &self,
+        request: OfferSaveRequest<'_>,
+    ) -> Result<OfferSaveResult, FunPayError>;
     async fn get_offer_edit_page(
         &self,
         golden_key: &str,
@@ -56,16 +121,37 @@ pub trait FunpayGateway: Send + Sync {
         user_agent: &str,
         node_id: i64,
     ) -> Result<String, FunPayError>;
+    async fn get_user_page(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        user_id: i64,
+    ) -> Result<String, FunPayError>;
+    /// Next page of a profile's reviews after the `continue` token.
+    async fn post_user_reviews(
+        &self,
+        golden_key: &str,
+        user_agent: &str,
+        user_id: i64,
+        continuation: &str,
+    ) -> Result<String, FunPayError>;
+    async fn post_lots_raise(
+        &self,
+        request: RaiseRequest<'_>,
+    ) -> Result<RaiseResponse, FunPayError>;
     async fn calc_price(
         &self,
         golden_key: &str,
         user_agent: &str,
         node_id: i64,
-        price: f64,
-    ) -> Result<Value, FunPayError>;
+        price: Decimal,
+    ) -> Result<PriceCalculation, FunPayError>;
 }
 
 pub mod account;
 pub mod http;
+#[cfg(test)]
+pub(crate) mod mock;
 pub mod poller;
+pub mod session;
 pub mod urls;
@@ -1,18 +1,26 @@
+use crate::client::session::Session;
 use crate::client::FunpayGateway;
 use crate::error::FunPayError;
 use crate::events::Event;
 use crate::models::ids::{ChatId, OrderId};
-use crate::models::{ChatShortcut, Message, OrderShortcut};
-use crate::parsing::{parse_message_html, parse_orders_list};
+use crate::models::{ChatShortcut, Message, OrderShortcut, PurchaseShortcut};
+use crate::parsing::{parse_orders_list, parse_purchases_list, parse_runner_messages};
+use crate::runner::{
+    ChatBookmarks, ChatNode, OrdersCounters, RunnerObject, RunnerObjectResult, RunnerResponse,
+    EMPTY_TAG,
+};
 use crate::storage::StateStorage;
 use log::debug;
 use scraper::{Html, Selector};
-use serde_json::{json, to_string, Value};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::broadcast::Sender;
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
+
+/// How often the purchases list is re-fetched even though the order
+/// counters did not change, to catch status changes they do not show.
+const PURCHASES_RECHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
 
 pub struct FunPayPoller {
     pub gateway: Arc<dyn FunpayGateway>,
@@ -20,8 +28,7 @@ pub struct FunPayPoller {
     pub user_agent: String,
     pub id: i64,
     pub username: Option<String>,
-    pub csrf_token: String,
-    pub phpsessid: Option<String>,
+    pub session: Arc<Session>,
     pub events_tx: Sender<Event>,
     pub storage: Arc<dyn StateStorage>,
     pub polling_interval: Duration,
@@ -32,7 +39,15 @@ pub struct FunPayPoller {
     pub last_order_event_tag: String,
     pub last_messages: HashMap<i64, (i64, i64, Option<String>)>,
     pub last_messages_ids: HashMap<i64, i64>,
+    pub chat_node_tags: HashMap<i64, String>,
     pub saved_orders: HashMap<OrderId, OrderShortcut>,
+    /// `None` until the purchases list has been loaded once.
+    pub saved_purchases: Option<HashMap<OrderId, PurchaseShortcut>>,
+    /// Set when the order counters change, so the purchases list is
+    /// re-fetched on the next round.
+    pub purchases_changed: bool,
+    /// When the purchases list was last loaded.
+    pub purchases_fetched_at: Option<Instant>,
 }
 
 impl FunPayPoller {
@@ -46,21 +61,18 @@ impl FunPayPoller {
 
         let mut first = true;
         loop {
-            let orders = json!({
-                "type": "orders_counters",
-                "id": self.id,
-                "tag": self.last_order_event_tag,
-                "data": false
-            });
-            let chats = json!({
-                "type": "chat_bookmarks",
-                "id": self.id,
-                "tag": self.last_msg_event_tag,
-                "data": false
-            });
-            let objects_json = to_string(&json!([orders, chats])).unwrap();
+            let objects = [
+                RunnerObject::OrdersCounters(OrdersCounters {
+                    user_id: self.id,
+                    tag: self.last_order_event_tag.clone(),
+                }),
+                RunnerObject::ChatBookmarks(ChatBookmarks {
+                    user_id: self.id,
+                    tag: self.last_msg_event_tag.clone(),
+                }),
+            ];
 
-            let updates = match self.post_runner(objects_json).await {
+            let updates = match self.post_runner(&objects).await {
                 Ok(updates) => updates,
                 Err(e) => {
                     log::error!(target: "funpay_client", "HTTP request failed: {e}. Retrying in {:?}...", self.error_retry_delay);
@@ -90,7 +102,11 @@ impl FunPayPoller {
                             }
                             if !first {
                                 for m in msgs {
+                                    let system_event = Event::from_system_message(&m);
                                     let _ = self.events_tx.send(Event::NewMessage { message: m });
+                                    if let Some(ev) = system_event {
+                                        let _ = self.events_tx.send(ev);
+                                    }
                                 }
                             }
                         }
@@ -144,100 +160,93 @@ impl FunPayPoller {
                 }
             }
 
+            self.poll_purchases().await;
+
             first = false;
             sleep(self.polling_interval).await;
         }
     }
 
-    async fn post_runner(&self, objects_json: String) -> Result<Value, FunPayError> {
-        self.gateway
-            .post_runner(
-                &self.golden_key,
-                &self.user_agent,
-                &self.csrf_token,
-                self.phpsessid.as_deref(),
-                &objects_json,
-                None,
-            )
+    async fn post_runner(&self, objects: &[RunnerObject]) -> Result<RunnerResponse, FunPayError> {
+        self.session
+            .call(|tokens| async move {
+                self.gateway
+                    .post_runner(
+                        &self.golden_key,
+                        &self.user_agent,
+                        &tokens.csrf_token,
+                        tokens.phpsessid.as_deref(),
+                        objects,
+                        None,
+                    )
+                    .await
+            })
             .await
     }
 
     fn parse_events_from_updates(
         &mut self,
-        updates: &Value,
+        updates: &RunnerResponse,
         first: bool,
-    ) -> (Vec<Event>, Vec<(i64, Option<String>)>) {
+    ) -> (Vec<Event>, Vec<ChatShortcut>) {
         let mut events = Vec::new();
         let mut changed_chats: Vec<ChatShortcut> = Vec::new();
-        let objects = updates
-            .get("objects")
-            .and_then(|x| x.as_array())
-            .cloned()
-            .unwrap_or_default();
-        for obj in objects {
-            let typ = obj.get("type").and_then(|x| x.as_str()).unwrap_or("");
-            if typ == "chat_bookmarks" {
-                if let Some(tag) = obj.get("tag").and_then(|x| x.as_str()) {
-                    self.last_msg_event_tag = tag.to_string();
-                }
-                let html = obj
-                    .get("data")
-                    .and_then(|x| x.get("html"))
-                    .and_then(|x| x.as_str())
-                    .unwrap_or("");
-                if html.is_empty() {
-                    continue;
-                }
-                let chats = self.parse_chat_bookmarks(html);
-                if first {
-                    for ch in chats {
-                        events.push(Event::InitialChat { chat: ch.clone() });
-                        if ch.node_msg_id > 0 {
-                            changed_chats.push(ch);
-                        }
+        for obj in &updates.objects {
+            match obj {
+                RunnerObjectResult::ChatBookmarks { tag, data } => {
+                    if let Some(tag) = tag {
+                        self.last_msg_event_tag = tag.clone();
                     }
-                } else {
-                    if !chats.is_empty() {
-                        events.push(Event::ChatsListChanged);
+                    let html = data.as_ref().map(|d| d.html.as_str()).unwrap_or("");
+                    if html.is_empty() {
+                        continue;
                     }
-                    for ch in chats {
-                        let prev = self
-                            .last_messages
-                            .get(&ch.id)
-                            .cloned()
-                            .unwrap_or((-1, -1, None));
-                        if ch.node_msg_id > prev.0 {
-                            events.push(Event::LastChatMessageChanged { chat: ch.clone() });
-                            changed_chats.push(ch.clone());
+                    let chats = self.parse_chat_bookmarks(html);
+                    if first {
+                        for ch in chats {
+                            events.push(Event::InitialChat { chat: ch.clone() });
+                            if ch.node_msg_id > 0 {
+                                changed_chats.push(ch);
+                            }
+                        }
+                    } else {
+                        if !chats.is_empty() {
+                            events.push(Event::ChatsListChanged);
+                        }
+                        for ch in chats {
+                            let prev = self
+                                .last_messages
+                                .get(&ch.id)
+                                .cloned()
+                                .unwrap_or((-1, -1, None));
+                            if ch.node_msg_id > prev.0 {
+                                events.push(Event::LastChatMessageChanged { chat: ch.clone() });
+                                changed_chats.push(ch.clone());
+                            }
+                            self.last_messages.insert(
+                                ch.id,
+                                (ch.node_msg_id, ch.user_msg_id, ch.last_message_text.clone()),
+                            );
                         }
-                        self.last_messages.insert(
-                            ch.id,
-                            (ch.node_msg_id, ch.user_msg_id, ch.last_message_text.clone()),
-                        );
                     }
                 }
-            } else if typ == "orders_counters" {
-                if let Some(tag) = obj.get("tag").and_then(|x| x.as_str()) {
-                    self.last_order_event_tag = tag.to_string();
+                RunnerObjectResult::OrdersCounters { tag, data } => {
+                    if let Some(tag) = tag {
+                        self.last_order_event_tag = tag.clone();
+                    }
+                    if data.is_some() {
+                        self.purchases_changed = true;
+                    }
+                    let counters = data.clone().unwrap_or_default();
+                    events.push(Event::OrdersListChanged {
+                        purchases: counters.buyer,
+                        sales: counters.seller,
+                    });
                 }
-                let purchases = obj
-                    .get("data")
-                    .and_then(|x| x.get("buyer"))
-                    .and_then(|x| x.as_i64())
-                    .unwrap_or(0) as i32;
-                let sales = obj
-                    .get("data")
-                    .and_then(|x| x.get("seller"))
-                    .and_then(|x| x.as_i64())
-                    .unwrap_or(0) as i32;
-                events.push(Event::OrdersListChanged { purchases, sales });
+                _ => {}
             }
         }
-        let chats_data: Vec<(i64, Option<String>)> = changed_chats
-            .into_iter()
-            .map(|c| (c.id, Some(c.name)))
-            .collect();
-        (events, chats_data)
+        (events, changed_chats)
     }
 
     fn parse_chat_bookmarks(&mut self, html: &str) -> Vec<ChatShortcut> {
@@ -291,61 +300,137 @@ impl FunPayPoller {
         parse_orders_list(&body, self.id)
     }
 
+    async fn fetch_purchases_list(&self) -> Result<Vec<PurchaseShortcut>, FunPayError> {
+        let body = self
+            .gateway
+            .get_orders_purchases(&self.golden_key, &self.user_agent)
+            .await?;
+        parse_purchases_list(&body, self.id)
+    }
+
+    /// Re-fetches the purchases list if the order counters changed since the
+    /// last fetch, or once `PURCHASES_RECHECK_INTERVAL` has passed.
+    async fn poll_purchases(&mut self) {
+        let due = self
+            .purchases_fetched_at
+            .is_none_or(|at| at.elapsed() >= PURCHASES_RECHECK_INTERVAL);
+        if !self.purchases_changed && !due {
+            return;
+        }
+        match self.fetch_purchases_list().await {
+            Ok(list) => {
+                self.purchases_changed = false;
+                self.purchases_fetched_at = Some(Instant::now());
+                self.update_purchases(list);
+            }
+            Err(e) => {
+                log::error!(target: "funpay_client", "Failed to fetch purchases list: {e}");
+            }
+        }
+    }
+
+    /// Emits purchase events against the previous snapshot. The first load
+    /// only records the current state.
+    fn update_purchases(&mut self, list: Vec<PurchaseShortcut>) {
+        let new_map: HashMap<OrderId, PurchaseShortcut> =
+            list.into_iter().map(|p| (p.id.clone(), p)).collect();
+        if let Some(saved) = &self.saved_purchases {
+            for (id, purchase) in new_map.iter() {
+                match saved.get(id) {
+                    Some(prev) if prev.status != purchase.status => {
+                        let _ = self.events_tx.send(Event::PurchaseStatusChanged {
+                            purchase: purchase.clone(),
+                        });
+                    }
+                    Some(_) => {}
+                    None => {
+                        let _ = self.events_tx.send(Event::NewPurchase {
+                            purchase: purchase.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        self.saved_purchases = Some(new_map);
+    }
+
+    /// Requests only the messages newer than the last known id of each chat.
+    /// FunPay ignores a cursor that has fallen out of the chat's visible
+    /// window, so chats that come back without the expected new messages are
+    /// re-requested in full.
     async fn fetch_chats_histories(
-        &self,
-        chats_data: &[(i64, Option<String>)],
+        &mut self,
+        chats: &[ChatShortcut],
     ) -> Result<HashMap<i64, Vec<Message>>, FunPayError> {
-        let mut objects = Vec::with_capacity(chats_data.len());
-        for (chat_id, _name) in chats_data.iter() {
-            objects.push(json!({
-                "type": "chat_node",
-                "id": chat_id,
-                "tag": "00000000",
-                "data": {"node": chat_id, "last_message": -1, "content": ""}
-            }));
+        let objects: Vec<RunnerObject> = chats
+            .iter()
+            .map(|ch| {
+                RunnerObject::ChatNode(ChatNode {
+                    node: ch.id.to_string(),
+                    last_message: self.last_messages_ids.get(&ch.id).copied().unwrap_or(-1),
+                    tag: self
+                        .chat_node_tags
+                        .get(&ch.id)
+                        .cloned()
+                        .unwrap_or_else(|| EMPTY_TAG.to_string()),
+                })
+            })
+            .collect();
+        let mut out = self.fetch_chat_nodes(&objects, chats).await?;
+
+        let stale: Vec<RunnerObject> = chats
+            .iter()
+            .filter(|ch| {
+                let Some(last_id) = self.last_messages_ids.get(&ch.id).copied() else {
+                    return false;
+                };
+                ch.node_msg_id > last_id
+                    && !out
+                        .get(&ch.id)
+                        .is_some_and(|msgs| msgs.iter().any(|m| m.id > last_id))
+            })
+            .map(|ch| RunnerObject::ChatNode(ChatNode::new(ch.id.to_string())))
+            .collect();
+        if !stale.is_empty() {
+            debug!(
+                target: "funpay_client",
+                "Incremental fetch returned no new messages for {} chat(s), falling back to full fetch",
+                stale.len()
+            );
+            out.extend(self.fetch_chat_nodes(&stale, chats).await?);
         }
-        let objects_json = to_string(&objects).unwrap();
-        let res = self.post_runner(objects_json).await?;
+        Ok(out)
+    }
+
+    async fn fetch_chat_nodes(
+        &mut self,
+        objects: &[RunnerObject],
+        chats: &[ChatShortcut],
+    ) -> Result<HashMap<i64, Vec<Message>>, FunPayError> {
+        let res = self.post_runner(objects).await?;
         let mut out: HashMap<i64, Vec<Message>> = HashMap::new();
-        let objects = res
-            .get("objects")
-            .and_then(|x| x.as_array())
-            .cloned()
-            .unwrap_or_default();
-        for obj in objects {
-            if obj.get("type").and_then(|x| x.as_str()) != Some("chat_node") {
+        for obj in res.objects {
+            let RunnerObjectResult::ChatNode {
+                id: node,
+                tag,
+                data,
+            } = obj
+            else {
                 continue;
+            };
+            let id = node.parse::<i64>().unwrap_or(0);
+            if let Some(tag) = tag {
+                self.chat_node_tags.insert(id, tag);
             }
-            let id = obj.get("id").and_then(|x| x.as_i64()).unwrap_or(0);
-            let data = obj.get("data");
-            if data.is_none() {
+            let Some(data) = data else {
                 out.insert(id, Vec::new());
                 continue;
-            }
-            let data = data.unwrap();
-            let messages = data
-                .get("messages")
-                .and_then(|x| x.as_array())
-                .cloned()
-                .unwrap_or_default();
-            let mut list = Vec::new();
-            for m in messages {
-                let mid = m.get("id").and_then(|x| x.as_i64()).unwrap_or(0);
-                let author_id = m.get("author").and_then(|x| x.as_i64()).unwrap_or(0);
-                let html = m.get("html").and_then(|x| x.as_str()).unwrap_or("");
-                let (text, _image) = parse_message_html(html);
-                list.push(Message {
-                    id: mid,
-                    chat_id: ChatId::from(format!("{id}")),
-                    chat_name: chats_data
-                        .iter()
-                        .find(|(cid, _)| *cid == id)
-                        .and_then(|(_, n)| n.clone()),
-                    text,
-                    interlocutor_id: None,
-                    author_id,
-                });
-            }
+            };
+            let chat_name = chats
+                .iter()
+                .find(|ch| ch.id == id)
+                .map(|ch| ch.name.as_str());
+            let list = parse_runner_messages(&ChatId::from(node), chat_name, &data.messages);
             out.insert(id, list);
         }
         Ok(out)
@@ -368,3 +453,130 @@ impl FunPayPoller {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::MockGateway;
+    use crate::client::session::SessionTokens;
+    use crate::models::enums::OrderStatus;
+    use crate::storage::memory::InMemoryStorage;
+    use crate::utils::random_tag;
+    use tokio::sync::broadcast::{self, error::TryRecvError, Receiver};
+
+    fn poller(gateway: &Arc<MockGateway>) -> (FunPayPoller, Receiver<Event>) {
+        let (events_tx, events_rx) = broadcast::channel(16);
+        let session = Session::new(
+            gateway.clone(),
+            "key".to_string(),
+            "ua".to_string(),
+            SessionTokens {
+                csrf_token: "csrf".to_string(),
+                phpsessid: None,
+            },
+        );
+        let poller = FunPayPoller {
+            gateway: gateway.clone(),
+            golden_key: "key".to_string(),
+            user_agent: "ua".to_string(),
+            id: 7,
+            username: None,
+            session: Arc::new(session),
+            events_tx,
+            storage: Arc::new(InMemoryStorage::new()),
+            polling_interval: Duration::from_millis(10),
+            error_retry_delay: Duration::from_millis(10),
+            last_msg_event_tag: random_tag(),
+            last_order_event_tag: random_tag(),
+            last_messages: HashMap::new(),
+            last_messages_ids: HashMap::new(),
+            chat_node_tags: HashMap::new(),
+            saved_orders: HashMap::new(),
+            saved_purchases: None,
+            purchases_changed: true,
+            purchases_fetched_at: None,
+        };
+        (poller, events_rx)
+    }
+
+    fn purchases(list: &[(&str, bool)]) -> String {
+        let items: String = list
+            .iter()
+            .map(|(id, paid)| {
+                format!(
+                    r#"<a class="tc-item{}" href="https://funpay.com/orders/{id}/">
+                    <div class="tc-order">#{id}</div>
+                    <div class="order-desc"><div>Gold</div><div class="text-muted">WoW, Gold</div></div>
+                    <div class="tc-user"><div class="media-user-name"><span data-href="https://funpay.com/users/42/">seller</span></div></div>
+                    <div class="tc-price">100 <span class="unit">₽</span></div>
+                    </a>"#,
+                    if *paid { " info" } else { "" }
+                )
+            })
+            .collect();
+        format!(r#"<html><body><div class="user-link-name">me</div>{items}</body></html>"#)
+    }
+
+    fn counters_changed(poller: &mut FunPayPoller) {
+        let updates: RunnerResponse = serde_json::from_str(
+            r#"{"objects":[{"type":"orders_counters","id":7,"tag":"t","data":{"buyer":1,"seller":0}}]}"#,
+        )
+        .unwrap();
+        poller.parse_events_from_updates(&updates, false);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_purchase_events() {
+        let gateway = Arc::new(MockGateway::new());
+        for page in [
+            purchases(&[("AAAA1111", true)]),
+            purchases(&[("AAAA1111", true), ("BBBB2222", true)]),
+            purchases(&[("AAAA1111", false), ("BBBB2222", true)]),
+            purchases(&[("AAAA1111", false), ("BBBB2222", false)]),
+        ] {
+            gateway.reply::<String>("get_orders_purchases", Ok(page));
+        }
+        let (mut poller, mut events) = poller(&gateway);
+
+        // The first load only records the current state.
+        poller.poll_purchases().await;
+        assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+
+        // Nothing is fetched until the order counters change.
+        poller.poll_purchases().await;
+        assert_eq!(gateway.count("get_orders_purchases"), 1);
+
+        counters_changed(&mut poller);
+        poller.poll_purchases().await;
+        match events.try_recv() {
+            Ok(Event::NewPurchase { purchase }) => assert_eq!(purchase.id.to_string(), "BBBB2222"),
+            other => panic!("unexpected event: {other:?}"),
+        }
+        assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+
+        counters_changed(&mut poller);
+        poller.poll_purchases().await;
+        match events.try_recv() {
+            Ok(Event::PurchaseStatusChanged { purchase }) => {
+                assert_eq!(purchase.id.to_string(), "AAAA1111");
+                assert_eq!(purchase.status, OrderStatus::Closed);
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+        assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+        assert_eq!(gateway.count("get_orders_purchases"), 3);
+
+        // A change the counters do not show is picked up on the slow timer.
+        tokio::time::advance(PURCHASES_RECHECK_INTERVAL - Duration::from_secs(1)).await;
+        poller.poll_purchases().await;
+        assert_eq!(gateway.count("get_orders_purchases"), 3);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        poller.poll_purchases().await;
+        match events.try_recv() {
+            Ok(Event::PurchaseStatusChanged { purchase }) => {
+                assert_eq!(purchase.id.to_string(), "BBBB2222");
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+}
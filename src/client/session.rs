@@ -0,0 +1,260 @@
+use crate::client::FunpayGateway;
+use crate::error::FunPayError;
+use crate::models::AppData;
+use crate::parsing::parse_app_data;
+use crate::utils::extract_phpsessid;
+use scraper::{Html, Selector};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct SessionTokens {
+    pub csrf_token: String,
+    pub phpsessid: Option<String>,
+}
+
+/// Home page data extracted during a (re)login.
+#[derive(Debug, Clone)]
+pub struct SessionSnapshot {
+    pub app_data: AppData,
+    pub username: String,
+    pub phpsessid: Option<String>,
+    pub body: String,
+}
+
+/// Refreshable csrf token / PHPSESSID pair shared by every clone of
+/// `FunPaySender` and by the poller.
+pub struct Session {
+    gateway: Arc<dyn FunpayGateway>,
+    golden_key: String,
+    user_agent: String,
+    tokens: RwLock<SessionTokens>,
+    generation: AtomicU64,
+    refresh_lock: Mutex<()>,
+}
+
+impl Session {
+    pub fn new(
+        gateway: Arc<dyn FunpayGateway>,
+        golden_key: String,
+        user_agent: String,
+        tokens: SessionTokens,
+    ) -> Self {
+        Self {
+            gateway,
+            golden_key,
+            user_agent,
+            tokens: RwLock::new(tokens),
+            generation: AtomicU64::new(0),
+            refresh_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn tokens(&self) -> SessionTokens {
+        self.tokens.read().unwrap().clone()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Fetches the home page and parses the account data from it.
+    pub async fn login(
+        gateway: &dyn FunpayGateway,
+        golden_key: &str,
+        user_agent: &str,
+    ) -> Result<SessionSnapshot, FunPayError> {
+        let (body, set_cookies) = gateway.get_home(golden_key, user_agent).await?;
+        let phpsessid = extract_phpsessid(&set_cookies);
+        let html = Html::parse_document(&body);
+        let app_data = parse_app_data(&html)?;
+        let sel_uname = Selector::parse("div.user-link-name").unwrap();
+        let username = html
+            .select(&sel_uname)
+            .next()
+            .map(|n| n.text().collect::<String>())
+            .ok_or(FunPayError::Unauthorized)?;
+        let app_data =
+            app_data.ok_or_else(|| FunPayError::Parse(String::from("missing app data")))?;
+        Ok(SessionSnapshot {
+            app_data,
+            username,
+            phpsessid,
+            body,
+        })
+    }
+
+    /// Re-fetches the tokens unless another caller already did it since
+    /// `seen_generation` was observed.
+    pub async fn refresh(&self, seen_generation: u64) -> Result<(), FunPayError> {
+        let _guard = self.refresh_lock.lock().await;
+        if self.generation() != seen_generation {
+            return Ok(());
+        }
+
+        log::warn!(target: "funpay_client", "Session expired, refreshing csrf token and PHPSESSID");
+        let snapshot =
+            Self::login(self.gateway.as_ref(), &self.golden_key, &self.user_agent).await?;
+        {
+            let mut tokens = self.tokens.write().unwrap();
+            tokens.csrf_token = snapshot.app_data.csrf_token;
+            if snapshot.phpsessid.is_some() {
+                tokens.phpsessid = snapshot.phpsessid;
+            }
+        }
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        Ok(())
+    }
+
+    /// Runs `f` with the current tokens, refreshing the session and retrying
+    /// once if the call failed because the tokens expired. FunPay applies
+    /// nothing it rejects for a stale session, so the retry cannot apply a
+    /// call twice; transport errors and 5xx are returned without a retry.
+    pub async fn call<T, F, Fut>(&self, f: F) -> Result<T, FunPayError>
+    where
+        F: Fn(SessionTokens) -> Fut,
+        Fut: Future<Output = Result<T, FunPayError>>,
+    {
+        let generation = self.generation();
+        match f(self.tokens()).await {
+            Err(e) if e.is_session_expired() => {
+                log::debug!(target: "funpay_client", "Request rejected with stale session: {e}");
+                self.refresh(generation).await?;
+                f(self.tokens()).await
+            }
+            res => res,
+        }
+    }
+
+    /// Runs `f` exactly once. A stale session is still refreshed for later
+    /// calls, but the request is not resent: for calls that move money,
+    /// where even a rejected attempt should be looked at before trying again.
+    pub async fn call_once<T, F, Fut>(&self, f: F) -> Result<T, FunPayError>
+    where
+        F: FnOnce(SessionTokens) -> Fut,
+        Fut: Future<Output = Result<T, FunPayError>>,
+    {
+        let generation = self.generation();
+        let res = f(self.tokens()).await;
+        if let Err(e) = &res {
+            if e.is_session_expired() {
+                if let Err(refresh_err) = self.refresh(generation).await {
+                    log::warn!(target: "funpay_client", "Failed to refresh session: {refresh_err}");
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::MockGateway;
+    use reqwest::StatusCode;
+    use std::sync::atomic::AtomicUsize;
+
+    fn session(gateway: Arc<MockGateway>) -> Session {
+        Session::new(
+            gateway,
+            "key".to_string(),
+            "ua".to_string(),
+            SessionTokens {
+                csrf_token: "old".to_string(),
+                phpsessid: Some("sess0".to_string()),
+            },
+        )
+    }
+
+    fn failed(body: &str) -> FunPayError {
+        FunPayError::RequestFailed {
+            status: StatusCode::BAD_REQUEST,
+            body: body.to_string(),
+            url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_session_expiry_detection() {
+        assert!(FunPayError::Unauthorized.is_session_expired());
+        let forbidden = FunPayError::RequestFailed {
+            status: StatusCode::FORBIDDEN,
+            body: String::new(),
+            url: String::new(),
+        };
+        assert!(forbidden.is_session_expired());
+        assert!(failed(r#"{"error":true,"msg":"Invalid csrf token"}"#).is_session_expired());
+        let html = r#"<body data-app-data='{"csrf-token":"abc"}'>Server error</body>"#;
+        assert!(!failed(html).is_session_expired());
+        assert!(!failed(r#"{"error":"Not enough money"}"#).is_session_expired());
+    }
+
+    #[tokio::test]
+    async fn test_session_refresh_generation() {
+        let gateway = Arc::new(MockGateway::new());
+        gateway.reply_home(7, "new", &["PHPSESSID=sess1; path=/"]);
+        let session = session(gateway.clone());
+
+        session.refresh(0).await.unwrap();
+        assert_eq!(session.generation(), 1);
+        assert_eq!(session.tokens().csrf_token, "new");
+        assert_eq!(session.tokens().phpsessid.as_deref(), Some("sess1"));
+
+        // Someone else already refreshed since generation 0 was seen.
+        session.refresh(0).await.unwrap();
+        assert_eq!(session.generation(), 1);
+        assert_eq!(gateway.count("get_home"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_call_retries_once() {
+        let gateway = Arc::new(MockGateway::new());
+        gateway.reply_home(7, "new", &[]);
+        let session = session(gateway.clone());
+
+        let attempts = AtomicUsize::new(0);
+        let res: Result<(), _> = session
+            .call(|tokens| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    assert!(tokens.csrf_token == "old" || tokens.csrf_token == "new");
+                    Err(FunPayError::Unauthorized)
+                }
+            })
+            .await;
+        assert!(matches!(res, Err(FunPayError::Unauthorized)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(gateway.count("get_home"), 1);
+
+        let attempts = AtomicUsize::new(0);
+        let res: Result<(), _> = session
+            .call(|_| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                async { Err(failed("<html>csrf-token</html>")) }
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(gateway.count("get_home"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_call_once_refreshes_without_resending() {
+        let gateway = Arc::new(MockGateway::new());
+        gateway.reply_home(7, "new", &[]);
+        let session = session(gateway.clone());
+
+        let attempts = AtomicUsize::new(0);
+        let res: Result<(), _> = session
+            .call_once(|_| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                async { Err(FunPayError::Unauthorized) }
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(session.tokens().csrf_token, "new");
+    }
+}
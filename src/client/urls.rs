@@ -31,10 +31,39 @@ impl UrlBuilder {
         format!("{}/runner/", self.base_url)
     }
 
+    pub fn orders_purchases(&self) -> String {
+        format!("{}/orders/", self.base_url)
+    }
+
     pub fn orders_trade(&self) -> String {
         format!("{}/orders/trade", self.base_url)
     }
 
+    pub fn order_review(&self) -> String {
+        format!("{}/orders/review", self.base_url)
+    }
+
+    pub fn order_review_delete(&self) -> String {
+        format!("{}/orders/reviewDelete", self.base_url)
+    }
+
+    pub fn order_refund(&self) -> String {
+        format!("{}/orders/refund", self.base_url)
+    }
+
+    /// The sales list with filter parameters.
+    pub fn orders_trade_filtered(&self, query: &[(&str, String)]) -> String {
+        if query.is_empty() {
+            return self.orders_trade();
+        }
+        let query = query
+            .iter()
+            .map(|(k, v)| format!("{k}={}", urlencoding::encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        format!("{}?{query}", self.orders_trade())
+    }
+
     pub fn order_page(&self, order_id: &str) -> String {
         format!("{}/orders/{order_id}/", self.base_url)
     }
@@ -43,7 +72,26 @@ impl UrlBuilder {
         format!("{}/chat/?node={chat_id}", self.base_url)
     }
 
+    pub fn chat_history(&self, chat_id: &str, last_message: Option<i64>) -> String {
+        match last_message {
+            Some(id) => format!(
+                "{}/chat/history?node={chat_id}&last_message={id}",
+                self.base_url
+            ),
+            None => format!("{}/chat/history?node={chat_id}", self.base_url),
+        }
+    }
+
+    pub fn chat_image_upload(&self) -> String {
+        format!("{}/file/addChatImage", self.base_url)
+    }
+
+    /// Edit form of an existing offer, or the blank form for a new one when
+    /// `offer_id` is 0.
     pub fn offer_edit(&self, node_id: i64, offer_id: i64) -> String {
+        if offer_id == 0 {
+            return format!("{}/lots/offerEdit?node={node_id}", self.base_url);
+        }
         format!(
             "{}/lots/offerEdit?node={node_id}&offer={offer_id}",
             self.base_url
@@ -65,4 +113,28 @@ impl UrlBuilder {
     pub fn lots_calc(&self) -> String {
         format!("{}/lots/calc", self.base_url)
     }
+
+    pub fn lots_raise(&self) -> String {
+        format!("{}/lots/raise", self.base_url)
+    }
+
+    pub fn user_page(&self, user_id: i64) -> String {
+        format!("{}/users/{user_id}/", self.base_url)
+    }
+
+    pub fn account_balance(&self) -> String {
+        format!("{}/account/balance", self.base_url)
+    }
+
+    pub fn withdraw_calc(&self) -> String {
+        format!("{}/withdraw/calc", self.base_url)
+    }
+
+    pub fn withdraw(&self) -> String {
+        format!("{}/withdraw/withdraw", self.base_url)
+    }
+
+    pub fn user_reviews(&self) -> String {
+        format!("{}/users/reviews", self.base_url)
+    }
 }
@@ -1,4 +1,6 @@
+use crate::withdrawal::WithdrawalGuard;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 pub struct FunPayConfig {
@@ -11,6 +13,12 @@ pub struct FunPayConfig {
     pub error_retry_delay: Duration,
     pub event_channel_capacity: usize,
     pub state_storage_path: Option<PathBuf>,
+    /// Prefix sent messages and review replies with the invisible
+    /// `BOT_MARKER`, so they are parsed back with `by_bot` set.
+    pub bot_marker: bool,
+    /// Limits for `FunPaySender::request_withdrawal`; withdrawals are
+    /// refused without one. Every sender of the account shares it.
+    pub withdrawal_guard: Option<Arc<WithdrawalGuard>>,
 }
 
 impl Default for FunPayConfig {
@@ -25,6 +33,8 @@ impl Default for FunPayConfig {
             error_retry_delay: Duration::from_secs(5),
             event_channel_capacity: 512,
             state_storage_path: None,
+            bot_marker: false,
+            withdrawal_guard: None,
         }
     }
 }
@@ -82,6 +92,16 @@ impl FunPayConfigBuilder {
         self
     }
 
+    pub fn bot_marker(mut self, enabled: bool) -> Self {
+        self.config.bot_marker = enabled;
+        self
+    }
+
+    pub fn withdrawal_guard(mut self, guard: WithdrawalGuard) -> Self {
+        self.config.withdrawal_guard = Some(Arc::new(guard));
+        self
+    }
+
     pub fn build(self) -> FunPayConfig {
         self.config
     }
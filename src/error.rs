@@ -1,4 +1,6 @@
 use reqwest::StatusCode;
+use serde_json::Value;
+use std::collections::BTreeMap;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -13,6 +15,24 @@ pub enum FunPayError {
     },
     #[error("account not initiated")]
     AccountNotInitiated,
+    #[error("runner action failed: {0}")]
+    RunnerAction(String),
+    #[error("offer validation failed: {field_errors:?}")]
+    OfferValidation {
+        field_errors: BTreeMap<String, String>,
+    },
+    #[error("offer created but its id is unknown; candidates: {candidates:?}")]
+    OfferIdUnknown { candidates: Vec<i64> },
+    #[error("raising lots failed: {0}")]
+    RaiseFailed(String),
+    #[error("refund failed: {0}")]
+    RefundFailed(String),
+    #[error("review reply failed: {0}")]
+    ReviewFailed(String),
+    #[error("withdrawal denied: {0}")]
+    WithdrawalDenied(String),
+    #[error("withdrawal failed: {0}")]
+    WithdrawalFailed(String),
     #[error("parse error: {0}")]
     Parse(String),
     #[error("io: {0}")]
@@ -22,3 +42,26 @@ pub enum FunPayError {
     #[error("middleware: {0}")]
     Middleware(#[from] reqwest_middleware::Error),
 }
+
+impl FunPayError {
+    /// Whether the error means the csrf token or PHPSESSID were rotated and
+    /// the session has to be refreshed: a 403, a redirect to the login page,
+    /// or a JSON error naming the csrf token. HTML error pages do not count
+    /// even though every page embeds a csrf token.
+    pub fn is_session_expired(&self) -> bool {
+        match self {
+            FunPayError::Unauthorized => true,
+            FunPayError::RequestFailed { status, .. } if *status == StatusCode::FORBIDDEN => true,
+            FunPayError::RequestFailed { body, .. } => {
+                serde_json::from_str::<Value>(body).ok().is_some_and(|v| {
+                    ["error", "msg"].iter().any(|key| {
+                        v.get(key)
+                            .and_then(Value::as_str)
+                            .is_some_and(|s| s.to_lowercase().contains("csrf"))
+                    })
+                })
+            }
+            _ => false,
+        }
+    }
+}
@@ -1,13 +1,92 @@
-use crate::models::{ChatShortcut, Message, OrderShortcut};
+use crate::models::ids::OrderId;
+use crate::models::{
+    ChatShortcut, Message, MessageKind, OrderShortcut, PurchaseShortcut, SystemEvent,
+};
+use crate::repricer::RepriceChange;
 
 #[derive(Debug, Clone)]
 pub enum Event {
-    InitialChat { chat: ChatShortcut },
+    InitialChat {
+        chat: ChatShortcut,
+    },
     ChatsListChanged,
-    LastChatMessageChanged { chat: ChatShortcut },
-    NewMessage { message: Message },
-    InitialOrder { order: OrderShortcut },
-    OrdersListChanged { purchases: i32, sales: i32 },
-    NewOrder { order: OrderShortcut },
-    OrderStatusChanged { order: OrderShortcut },
+    LastChatMessageChanged {
+        chat: ChatShortcut,
+    },
+    NewMessage {
+        message: Message,
+    },
+    InitialOrder {
+        order: OrderShortcut,
+    },
+    OrdersListChanged {
+        purchases: i32,
+        sales: i32,
+    },
+    NewOrder {
+        order: OrderShortcut,
+    },
+    OrderStatusChanged {
+        order: OrderShortcut,
+    },
+    NewPurchase {
+        purchase: PurchaseShortcut,
+    },
+    /// Found by diffing the purchases list, which is re-fetched when the
+    /// order counters change and otherwise only every few minutes, so a
+    /// change the counters do not show arrives late.
+    PurchaseStatusChanged {
+        purchase: PurchaseShortcut,
+    },
+    OrderPaid {
+        order_id: OrderId,
+        message: Message,
+    },
+    OrderConfirmed {
+        order_id: OrderId,
+        message: Message,
+    },
+    ReviewLeft {
+        order_id: OrderId,
+        message: Message,
+    },
+    ReviewChanged {
+        order_id: OrderId,
+        message: Message,
+    },
+    OrderRefunded {
+        order_id: OrderId,
+        message: Message,
+    },
+    LotsRaised {
+        game_id: i64,
+        node_ids: Vec<i64>,
+    },
+    OfferRepriced {
+        change: RepriceChange,
+    },
+}
+
+impl Event {
+    /// Maps a FunPay system notice to its order lifecycle event.
+    pub fn from_system_message(message: &Message) -> Option<Event> {
+        let MessageKind::System { event } = &message.kind else {
+            return None;
+        };
+        let message = message.clone();
+        match event.clone() {
+            SystemEvent::OrderPaid { order_id } => Some(Event::OrderPaid { order_id, message }),
+            SystemEvent::OrderConfirmed { order_id } => {
+                Some(Event::OrderConfirmed { order_id, message })
+            }
+            SystemEvent::ReviewLeft { order_id } => Some(Event::ReviewLeft { order_id, message }),
+            SystemEvent::ReviewChanged { order_id } => {
+                Some(Event::ReviewChanged { order_id, message })
+            }
+            SystemEvent::OrderRefunded { order_id } => {
+                Some(Event::OrderRefunded { order_id, message })
+            }
+            SystemEvent::Other => None,
+        }
+    }
 }
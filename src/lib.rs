@@ -4,8 +4,13 @@ pub mod error;
 pub mod events;
 pub mod models;
 pub mod parsing;
+pub mod presence;
+pub mod repricer;
+pub mod reviews;
+pub mod runner;
 pub mod storage;
 pub mod utils;
+pub mod withdrawal;
 
 pub use client::account::{FunPayAccount, FunPaySender};
 pub use client::http::ReqwestGateway;
@@ -13,4 +18,5 @@ pub use client::FunpayGateway;
 pub use config::{FunPayConfig, FunPayConfigBuilder};
 pub use error::FunPayError;
 pub use events::Event;
+pub use rust_decimal::Decimal;
 pub use storage::StateStorage;
@@ -10,3 +10,21 @@ pub enum SubcategoryType {
     Common,
     Currency,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransactionKind {
+    Sale,
+    Purchase,
+    Withdrawal,
+    Refund,
+    Deposit,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransactionStatus {
+    Complete,
+    Waiting,
+    Cancelled,
+    Unknown,
+}
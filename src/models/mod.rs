@@ -1,9 +1,29 @@
 pub mod enums;
 pub mod ids;
+pub mod money;
 
-use crate::models::enums::OrderStatus;
+use crate::error::FunPayError;
+use crate::models::enums::{OrderStatus, SubcategoryType, TransactionKind, TransactionStatus};
 use crate::models::ids::{ChatId, OrderId};
-use std::collections::HashMap;
+use crate::models::money::{Currency, Money};
+use crate::parsing::money::parse_amount;
+use crate::runner::error_message;
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct AppData {
+    pub user_id: i64,
+    pub csrf_token: String,
+    pub locale: Option<String>,
+}
 
 #[derive(Debug, Clone)]
 pub struct OfferSaveRequest<'a> {
@@ -36,6 +56,12 @@ pub struct OfferEditParams {
     pub active: Option<bool>,
     pub location: Option<String>,
     pub deleted: Option<bool>,
+    /// Form fields without a dedicated struct field, keyed by form name (`fields[region]`).
+    /// `merge` keeps the current entries and overwrites those set in the update.
+    pub extra_fields: BTreeMap<String, String>,
+    /// Form names `merge` drops from the current values, e.g. to uncheck a
+    /// checkbox: unchecked boxes are simply left out of the form.
+    pub removed_fields: BTreeSet<String>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -66,6 +92,7 @@ pub struct OfferCustomField {
     pub field_type: OfferFieldType,
     pub value: String,
     pub options: Vec<OfferFieldOption>,
+    pub required: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -150,8 +177,127 @@ impl OfferEditParams {
         self
     }
 
-    pub fn merge(self, other: OfferEditParams) -> Self {
-        Self {
+    pub fn with_field(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.set_field(name, value);
+        self
+    }
+
+    pub fn without_field(mut self, name: &str) -> Self {
+        self.remove_field(name);
+        self
+    }
+
+    /// Sets a form field by name, routing known names to their struct field.
+    /// Bare names like `region` are treated as `fields[region]`.
+    pub fn set_field(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        let name = offer_field_name(name);
+        self.removed_fields.remove(&name);
+        if let Some(slot) = self.field_slot(&name) {
+            *slot = Some(value);
+        } else {
+            self.extra_fields.insert(name, value);
+        }
+    }
+
+    /// Unsets a form field here and marks it for removal, so that `merge`
+    /// also drops it from the current values.
+    pub fn remove_field(&mut self, name: &str) {
+        let name = offer_field_name(name);
+        self.clear_field(&name);
+        self.removed_fields.insert(name);
+    }
+
+    fn clear_field(&mut self, name: &str) {
+        if let Some(slot) = self.field_slot(name) {
+            *slot = None;
+        } else {
+            self.extra_fields.remove(name);
+        }
+    }
+
+    fn field_slot(&mut self, name: &str) -> Option<&mut Option<String>> {
+        match name {
+            "fields[quantity]" => Some(&mut self.quantity),
+            "fields[quantity2]" => Some(&mut self.quantity2),
+            "fields[method]" => Some(&mut self.method),
+            "fields[type]" => Some(&mut self.offer_type),
+            "server_id" => Some(&mut self.server_id),
+            "fields[desc][ru]" => Some(&mut self.desc_ru),
+            "fields[desc][en]" => Some(&mut self.desc_en),
+            "fields[payment_msg][ru]" => Some(&mut self.payment_msg_ru),
+            "fields[payment_msg][en]" => Some(&mut self.payment_msg_en),
+            "fields[summary][ru]" => Some(&mut self.summary_ru),
+            "fields[summary][en]" => Some(&mut self.summary_en),
+            "fields[game]" => Some(&mut self.game),
+            "fields[images]" => Some(&mut self.images),
+            "price" => Some(&mut self.price),
+            "location" => Some(&mut self.location),
+            _ => None,
+        }
+    }
+
+    /// All `fields[...]` values that are set, keyed by form name.
+    pub fn form_fields(&self) -> BTreeMap<String, String> {
+        let mut out = BTreeMap::new();
+        let known = [
+            ("fields[quantity]", &self.quantity),
+            ("fields[quantity2]", &self.quantity2),
+            ("fields[method]", &self.method),
+            ("fields[type]", &self.offer_type),
+            ("fields[desc][ru]", &self.desc_ru),
+            ("fields[desc][en]", &self.desc_en),
+            ("fields[payment_msg][ru]", &self.payment_msg_ru),
+            ("fields[payment_msg][en]", &self.payment_msg_en),
+            ("fields[summary][ru]", &self.summary_ru),
+            ("fields[summary][en]", &self.summary_en),
+            ("fields[game]", &self.game),
+            ("fields[images]", &self.images),
+        ];
+        for (name, value) in known {
+            if let Some(v) = value {
+                out.insert(name.to_string(), v.clone());
+            }
+        }
+        for (name, value) in &self.extra_fields {
+            if name.starts_with("fields[") {
+                out.insert(name.clone(), value.clone());
+            }
+        }
+        out
+    }
+
+    /// Every value the save form would carry, keyed by form name. Unset and
+    /// empty values are left out.
+    fn form_values(&self) -> BTreeMap<String, String> {
+        let mut out = self.form_fields();
+        let plain = [
+            ("price", &self.price),
+            ("server_id", &self.server_id),
+            ("location", &self.location),
+        ];
+        for (name, value) in plain {
+            if let Some(v) = value {
+                out.insert(name.to_string(), v.clone());
+            }
+        }
+        let flags = [
+            ("active", self.active),
+            ("deactivate_after_sale", self.deactivate_after_sale),
+            ("deleted", self.deleted),
+        ];
+        for (name, value) in flags {
+            if let Some(v) = value {
+                out.insert(name.to_string(), v.to_string());
+            }
+        }
+        out.retain(|_, v| !v.is_empty());
+        out
+    }
+
+    pub fn merge(self, mut other: OfferEditParams) -> Self {
+        let removed = std::mem::take(&mut other.removed_fields);
+        let mut merged = Self {
             quantity: other.quantity.filter(|s| !s.is_empty()).or(self.quantity),
             quantity2: other.quantity2.filter(|s| !s.is_empty()).or(self.quantity2),
             method: other.method.filter(|s| !s.is_empty()).or(self.method),
@@ -185,17 +331,726 @@ impl OfferEditParams {
             active: other.active.or(self.active),
             location: other.location.filter(|s| !s.is_empty()).or(self.location),
             deleted: other.deleted.or(self.deleted),
+            extra_fields: {
+                let mut fields = self.extra_fields;
+                fields.extend(other.extra_fields);
+                fields
+            },
+            removed_fields: BTreeSet::new(),
+        };
+        for name in &removed {
+            merged.clear_field(name);
+        }
+        merged
+    }
+}
+
+fn offer_field_name(name: &str) -> String {
+    match name {
+        "price" | "server_id" | "location" => name.to_string(),
+        _ if name.contains('[') => name.to_string(),
+        _ => format!("fields[{name}]"),
+    }
+}
+
+/// A new offer to be listed with `FunPaySender::create_offer`.
+#[derive(Debug, Clone, Default)]
+pub struct OfferDraft {
+    pub price: String,
+    pub quantity: Option<String>,
+    pub summary_ru: Option<String>,
+    pub summary_en: Option<String>,
+    pub desc_ru: Option<String>,
+    pub desc_en: Option<String>,
+    pub payment_msg_ru: Option<String>,
+    pub payment_msg_en: Option<String>,
+    pub images: Option<String>,
+    pub active: Option<bool>,
+    pub deactivate_after_sale: Option<bool>,
+    /// Category specific fields, keyed by form name (`fields[region]`) or bare name (`region`).
+    pub fields: BTreeMap<String, String>,
+}
+
+impl OfferDraft {
+    pub fn new(price: impl Into<String>) -> Self {
+        Self {
+            price: price.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_quantity(mut self, quantity: impl Into<String>) -> Self {
+        self.quantity = Some(quantity.into());
+        self
+    }
+
+    pub fn with_summary_ru(mut self, summary: impl Into<String>) -> Self {
+        self.summary_ru = Some(summary.into());
+        self
+    }
+
+    pub fn with_summary_en(mut self, summary: impl Into<String>) -> Self {
+        self.summary_en = Some(summary.into());
+        self
+    }
+
+    pub fn with_desc_ru(mut self, desc: impl Into<String>) -> Self {
+        self.desc_ru = Some(desc.into());
+        self
+    }
+
+    pub fn with_desc_en(mut self, desc: impl Into<String>) -> Self {
+        self.desc_en = Some(desc.into());
+        self
+    }
+
+    pub fn with_payment_msg_ru(mut self, msg: impl Into<String>) -> Self {
+        self.payment_msg_ru = Some(msg.into());
+        self
+    }
+
+    pub fn with_payment_msg_en(mut self, msg: impl Into<String>) -> Self {
+        self.payment_msg_en = Some(msg.into());
+        self
+    }
+
+    pub fn with_images(mut self, images: impl Into<String>) -> Self {
+        self.images = Some(images.into());
+        self
+    }
+
+    pub fn with_active(mut self, active: bool) -> Self {
+        self.active = Some(active);
+        self
+    }
+
+    pub fn with_deactivate_after_sale(mut self, deactivate: bool) -> Self {
+        self.deactivate_after_sale = Some(deactivate);
+        self
+    }
+
+    pub fn with_field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fields.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn into_edit_params(self) -> OfferEditParams {
+        let mut params = OfferEditParams {
+            price: Some(self.price),
+            quantity: self.quantity,
+            summary_ru: self.summary_ru,
+            summary_en: self.summary_en,
+            desc_ru: self.desc_ru,
+            desc_en: self.desc_en,
+            payment_msg_ru: self.payment_msg_ru,
+            payment_msg_en: self.payment_msg_en,
+            images: self.images,
+            active: self.active,
+            deactivate_after_sale: self.deactivate_after_sale,
+            ..Default::default()
+        };
+        for (name, value) in self.fields {
+            params.set_field(&name, value);
+        }
+        params
+    }
+}
+
+impl OfferFullParams {
+    /// Checks `values` (form name to value) against the form's custom fields:
+    /// select values must be one of the options, checkboxes must be boolean,
+    /// required fields must be set and every `fields[...]` name must exist on
+    /// the form. Returns the error message for each offending field.
+    pub fn validate_fields(
+        &self,
+        values: &BTreeMap<String, String>,
+    ) -> Result<(), BTreeMap<String, String>> {
+        let mut errors = BTreeMap::new();
+
+        for field in &self.custom_fields {
+            let value = values.get(&field.name).map(|v| v.trim()).unwrap_or("");
+            if value.is_empty() {
+                if field.required {
+                    errors.insert(
+                        field.name.clone(),
+                        String::from("required field is missing"),
+                    );
+                }
+                continue;
+            }
+            match field.field_type {
+                OfferFieldType::Select if !field.options.iter().any(|o| o.value == value) => {
+                    errors.insert(field.name.clone(), format!("unknown option {value:?}"));
+                }
+                // FunPay ticks any checkbox that is submitted at all.
+                OfferFieldType::Checkbox if matches!(value, "false" | "0") => {
+                    errors.insert(
+                        field.name.clone(),
+                        format!("got {value:?}; remove the field to uncheck it"),
+                    );
+                }
+                OfferFieldType::Checkbox
+                    if !matches!(value, "on" | "true" | "1")
+                        && !field.options.iter().any(|o| o.value == value) =>
+                {
+                    errors.insert(
+                        field.name.clone(),
+                        format!("expected a checkbox value, got {value:?}"),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        for (name, value) in values {
+            if value.is_empty() || is_builtin_offer_field(name) {
+                continue;
+            }
+            if !self.custom_fields.iter().any(|f| &f.name == name) {
+                errors.insert(name.clone(), String::from("unknown field"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Current values of the custom fields, keyed by form name.
+    pub fn field_values(&self) -> BTreeMap<String, String> {
+        self.custom_fields
+            .iter()
+            .map(|f| (f.name.clone(), f.value.clone()))
+            .collect()
+    }
+
+    /// Validates the form as it would be submitted with `params` applied on
+    /// top of the current field values.
+    pub fn validate(&self, params: &OfferEditParams) -> Result<(), FunPayError> {
+        let mut values = self.field_values();
+        for name in &params.removed_fields {
+            values.remove(name);
+        }
+        values.extend(params.form_fields());
+        self.validate_fields(&values)
+            .map_err(|field_errors| FunPayError::OfferValidation { field_errors })
+    }
+}
+
+/// Fields every offer form has regardless of category; they are never
+/// reported as unknown even when the category form hides them.
+pub(crate) fn is_builtin_offer_field(name: &str) -> bool {
+    matches!(
+        name,
+        "fields[quantity]"
+            | "fields[quantity2]"
+            | "fields[method]"
+            | "fields[type]"
+            | "fields[desc][ru]"
+            | "fields[desc][en]"
+            | "fields[payment_msg][ru]"
+            | "fields[payment_msg][en]"
+            | "fields[summary][ru]"
+            | "fields[summary][en]"
+            | "fields[game]"
+            | "fields[images]"
+    )
+}
+
+/// Response of `offerSave`: either `{done, url}` or `{error, errors: {field: msg}}`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OfferSaveResult {
+    #[serde(default)]
+    pub done: bool,
+    #[serde(default, deserialize_with = "error_message")]
+    pub error: Option<String>,
+    #[serde(default, deserialize_with = "error_message")]
+    pub msg: Option<String>,
+    #[serde(default, deserialize_with = "field_errors")]
+    pub errors: BTreeMap<String, String>,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+impl OfferSaveResult {
+    /// Turns a rejected save into `FunPayError::OfferValidation`. A general
+    /// error without per-field messages is reported under the `error` key.
+    pub fn into_result(self) -> Result<Self, FunPayError> {
+        if self.error.is_none() && self.errors.is_empty() {
+            return Ok(self);
+        }
+        let mut field_errors = self.errors;
+        if field_errors.is_empty() {
+            let message = self
+                .msg
+                .or(self.error)
+                .unwrap_or_else(|| String::from("offer was not saved"));
+            field_errors.insert(String::from("error"), message);
+        }
+        Err(FunPayError::OfferValidation { field_errors })
+    }
+
+    /// Id of the saved offer, taken from the redirect url.
+    pub fn offer_id(&self) -> Option<i64> {
+        let re_id = Regex::new(r"[?&](?:id|offer)=(\d+)").unwrap();
+        self.url
+            .as_deref()
+            .and_then(|u| re_id.captures(u))
+            .and_then(|c| c[1].parse::<i64>().ok())
+    }
+}
+
+/// PHP encodes an empty map as `[]`; messages may also come as lists.
+fn field_errors<'de, D>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Value::Object(map) = Value::deserialize(deserializer)? else {
+        return Ok(BTreeMap::new());
+    };
+    Ok(map
+        .into_iter()
+        .map(|(name, msg)| {
+            let msg = match msg {
+                Value::String(s) => s,
+                Value::Array(items) => items
+                    .iter()
+                    .map(|v| {
+                        v.as_str()
+                            .map(str::to_string)
+                            .unwrap_or_else(|| v.to_string())
+                    })
+                    .collect::<Vec<_>>()
+                    .join("; "),
+                v => v.to_string(),
+            };
+            (name, msg)
+        })
+        .collect())
+}
+
+/// Field-by-field difference between an offer's current params and the
+/// params that would be saved, keyed by form name (`price`, `fields[region]`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OfferChangeSet {
+    pub changes: Vec<OfferFieldChange>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfferFieldChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl OfferChangeSet {
+    pub fn between(current: &OfferEditParams, merged: &OfferEditParams) -> Self {
+        let old = current.form_values();
+        let new = merged.form_values();
+        let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        let changes = names
+            .into_iter()
+            .filter(|name| old.get(*name) != new.get(*name))
+            .map(|name| OfferFieldChange {
+                field: name.clone(),
+                old: old.get(name).cloned(),
+                new: new.get(name).cloned(),
+            })
+            .collect();
+        Self { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn get(&self, field: &str) -> Option<&OfferFieldChange> {
+        self.changes.iter().find(|c| c.field == field)
+    }
+}
+
+impl fmt::Display for OfferChangeSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return write!(f, "no changes");
+        }
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{}: {} -> {}",
+                change.field,
+                change.old.as_deref().unwrap_or("<empty>"),
+                change.new.as_deref().unwrap_or("<empty>")
+            )?;
         }
+        Ok(())
     }
 }
 
+/// Outcome of `FunPaySender::edit_offer`: what was changed and FunPay's reply.
+#[derive(Debug, Clone)]
+pub struct OfferEditResult {
+    pub changes: OfferChangeSet,
+    pub save: OfferSaveResult,
+}
+
+/// Settings for `FunPaySender::bulk_edit_offers`.
+#[derive(Debug, Clone)]
+pub struct BulkEditOptions {
+    /// How many offers are processed at the same time.
+    pub concurrency: usize,
+    /// Minimum delay between two requests to FunPay, shared by all workers.
+    pub min_interval: Duration,
+    /// Only fetch and merge the params, never post them.
+    pub dry_run: bool,
+}
+
+impl Default for BulkEditOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            min_interval: Duration::from_millis(250),
+            dry_run: false,
+        }
+    }
+}
+
+impl BulkEditOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct BulkEditResult {
+    pub offer_id: i64,
+    pub node_id: i64,
+    pub outcome: Result<BulkEditOutcome, FunPayError>,
+}
+
+#[derive(Debug, Clone)]
+pub enum BulkEditOutcome {
+    Saved(OfferEditResult),
+    /// Dry run: the changes that would have been posted.
+    Preview(OfferChangeSet),
+}
+
+/// Typed `lots/calc` response: what buyers pay per payment method when the
+/// offer's seller price is `price`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PriceCalculation {
+    /// Seller-side price the calculation was made for.
+    #[serde(skip)]
+    pub price: Decimal,
+    #[serde(default)]
+    pub methods: Vec<PaymentMethodPrice>,
+    /// Minimal seller price allowed in the category.
+    #[serde(default, rename = "minPrice", deserialize_with = "optional_price")]
+    pub min_price: Option<Decimal>,
+    #[serde(default, deserialize_with = "error_message")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PaymentMethodPrice {
+    #[serde(default)]
+    pub name: String,
+    #[serde(deserialize_with = "price")]
+    pub price: Decimal,
+    #[serde(default)]
+    pub unit: String,
+    #[serde(default)]
+    pub sort: Option<i64>,
+}
+
+impl PriceCalculation {
+    /// Cheapest price a buyer can pay.
+    pub fn buyer_price(&self) -> Option<Decimal> {
+        self.methods.iter().map(|m| m.price).min()
+    }
+
+    /// Buyer price divided by seller price, for the cheapest payment method.
+    pub fn commission_coefficient(&self) -> Option<Decimal> {
+        self.buyer_price()
+            .filter(|_| self.price > Decimal::ZERO)
+            .map(|buyer| buyer / self.price)
+    }
+
+    pub fn commission_percent(&self) -> Option<Decimal> {
+        self.commission_coefficient()
+            .map(|c| ((c - Decimal::ONE) * Decimal::ONE_HUNDRED).round_dp(2))
+    }
+
+    /// Seller price, rounded down to kopecks, at which the cheapest buyer
+    /// price does not exceed `buyer_price`. Assumes the commission is
+    /// proportional, which holds within a category up to rounding.
+    pub fn seller_price_for(&self, buyer_price: Decimal) -> Option<Decimal> {
+        let coefficient = self.commission_coefficient()?;
+        Some((buyer_price / coefficient).round_dp_with_strategy(2, RoundingStrategy::ToZero))
+    }
+}
+
+/// FunPay formats calc prices as text with thousand separators (`1 052.63`).
+fn price<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    optional_price(deserializer)?.ok_or_else(|| serde::de::Error::custom("expected a price"))
+}
+
+fn optional_price<'de, D>(deserializer: D) -> Result<Option<Decimal>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Value::deserialize(deserializer)? {
+        Value::Number(n) => Decimal::from_str(&n.to_string()).ok(),
+        Value::String(s) => parse_amount(&s),
+        _ => None,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct RaiseRequest<'a> {
+    pub golden_key: &'a str,
+    pub user_agent: &'a str,
+    pub phpsessid: Option<&'a str>,
+    pub csrf: &'a str,
+    pub game_id: i64,
+    pub node_id: i64,
+    /// Subcategories to raise, sent as `node_ids[]`.
+    pub node_ids: &'a [i64],
+}
+
+/// Response of `lots/raise`: either a success message, a "wait N hours"
+/// error, or a `modal` asking which subcategories to raise.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RaiseResponse {
+    #[serde(default, deserialize_with = "error_message")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub msg: Option<String>,
+    #[serde(default)]
+    pub modal: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RaiseResult {
+    pub game_id: i64,
+    /// Subcategories that were raised; empty while the cooldown is running.
+    pub raised: Vec<i64>,
+    /// When the game can be raised again, if FunPay reported a cooldown.
+    pub next_available_at: Option<DateTime<FixedOffset>>,
+    pub message: Option<String>,
+}
+
+/// Response of `orders/refund`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RefundResponse {
+    #[serde(default, deserialize_with = "error_message")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub msg: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RefundResult {
+    pub order_id: OrderId,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrencyBalance {
+    pub currency: Currency,
+    /// Can be withdrawn right now.
+    pub available: Decimal,
+    /// Held until the related orders are closed.
+    pub frozen: Decimal,
+}
+
+impl CurrencyBalance {
+    pub fn total(&self) -> Decimal {
+        self.available + self.frozen
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountBalance {
+    pub balances: Vec<CurrencyBalance>,
+}
+
+impl AccountBalance {
+    pub fn get(&self, currency: Currency) -> Option<&CurrencyBalance> {
+        self.balances.iter().find(|b| b.currency == currency)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WithdrawalRequest<'a> {
+    pub golden_key: &'a str,
+    pub user_agent: &'a str,
+    pub phpsessid: Option<&'a str>,
+    pub csrf: &'a str,
+    pub currency: Currency,
+    pub amount: Decimal,
+    /// FunPay payout method, sent as `ext_currency_id` (e.g. `card_rub`).
+    pub method: &'a str,
+    pub wallet: &'a str,
+}
+
+/// Response of `withdraw/calc` and `withdraw/withdraw`. `amount_ext` is what
+/// arrives in the wallet after fees.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WithdrawalResponse {
+    #[serde(default, deserialize_with = "error_message")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub msg: Option<String>,
+    #[serde(default, deserialize_with = "optional_price")]
+    pub amount_ext: Option<Decimal>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WithdrawalResult {
+    pub currency: Currency,
+    pub amount: Decimal,
+    pub method: String,
+    pub wallet: String,
+    /// Amount the wallet receives after FunPay's fee, as previewed.
+    pub receive_amount: Option<Decimal>,
+    pub message: Option<String>,
+}
+
+/// An entry of the `/account/balance` history. `amount` is signed: incoming
+/// money is positive, outgoing negative.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub id: i64,
+    pub kind: TransactionKind,
+    pub status: TransactionStatus,
+    pub title: String,
+    pub amount: Money,
+    pub order_id: Option<OrderId>,
+    pub date_text: String,
+    pub created_at: Option<DateTime<FixedOffset>>,
+}
+
+/// Client-side filter for the balance history. The history is newest first,
+/// so paging stops once entries get older than `from`.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionsFilter {
+    pub kinds: Option<Vec<TransactionKind>>,
+    pub status: Option<TransactionStatus>,
+    pub from: Option<DateTime<FixedOffset>>,
+    pub to: Option<DateTime<FixedOffset>>,
+}
+
+impl TransactionsFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_kind(mut self, kind: TransactionKind) -> Self {
+        self.kinds.get_or_insert_with(Vec::new).push(kind);
+        self
+    }
+
+    pub fn with_status(mut self, status: TransactionStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn with_from(mut self, from: DateTime<FixedOffset>) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    pub fn with_to(mut self, to: DateTime<FixedOffset>) -> Self {
+        self.to = Some(to);
+        self
+    }
+
+    /// Entries without a parsed date pass the date range.
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        if self
+            .kinds
+            .as_ref()
+            .is_some_and(|kinds| !kinds.contains(&transaction.kind))
+        {
+            return false;
+        }
+        if self.status.is_some_and(|s| s != transaction.status) {
+            return false;
+        }
+        let Some(created_at) = transaction.created_at else {
+            return true;
+        };
+        self.from.is_none_or(|from| created_at >= from) && self.to.is_none_or(|to| created_at <= to)
+    }
+}
+
+/// One page of the balance history, with the `continuation` token of the
+/// next page unless this is the last one.
+#[derive(Debug, Clone)]
+pub struct TransactionsPage {
+    pub transactions: Vec<Transaction>,
+    pub continuation: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReviewRequest<'a> {
+    pub golden_key: &'a str,
+    pub user_agent: &'a str,
+    pub phpsessid: Option<&'a str>,
+    pub csrf: &'a str,
+    pub author_id: i64,
+    pub order_id: &'a str,
+    /// Reply text; `None` deletes our reply.
+    pub text: Option<&'a str>,
+}
+
+/// Response of `orders/review` and `orders/reviewDelete`. `content` is the
+/// re-rendered review block.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ReviewResponse {
+    #[serde(default, deserialize_with = "error_message")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub msg: Option<String>,
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Offer {
     pub id: i64,
     pub node_id: i64,
     pub description: String,
-    pub price: f64,
-    pub currency: String,
+    pub price: Money,
     pub active: bool,
 }
 
@@ -204,8 +1059,7 @@ pub struct MarketOffer {
     pub id: i64,
     pub node_id: i64,
     pub description: String,
-    pub price: f64,
-    pub currency: String,
+    pub price: Money,
     pub seller_id: i64,
     pub seller_name: String,
     pub seller_online: bool,
@@ -224,27 +1078,214 @@ pub struct ChatShortcut {
     pub unread: bool,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageKind {
+    Text,
+    Image {
+        url: String,
+    },
+    /// Notice posted by FunPay itself (order paid, order confirmed, review left...).
+    System {
+        event: SystemEvent,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SystemEvent {
+    OrderPaid {
+        order_id: OrderId,
+    },
+    OrderConfirmed {
+        order_id: OrderId,
+    },
+    ReviewLeft {
+        order_id: OrderId,
+    },
+    ReviewChanged {
+        order_id: OrderId,
+    },
+    OrderRefunded {
+        order_id: OrderId,
+    },
+    /// A notice this client does not recognize; the text is kept in `Message::text`.
+    Other,
+}
+
+impl SystemEvent {
+    pub fn order_id(&self) -> Option<&OrderId> {
+        match self {
+            SystemEvent::OrderPaid { order_id }
+            | SystemEvent::OrderConfirmed { order_id }
+            | SystemEvent::ReviewLeft { order_id }
+            | SystemEvent::ReviewChanged { order_id }
+            | SystemEvent::OrderRefunded { order_id } => Some(order_id),
+            SystemEvent::Other => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Message {
     pub id: i64,
     pub chat_id: ChatId,
     pub chat_name: Option<String>,
+    pub kind: MessageKind,
     pub text: Option<String>,
     pub interlocutor_id: Option<i64>,
     pub author_id: i64,
+    pub author_name: Option<String>,
+    /// Only set on the first message of a group, FunPay omits the header on the rest.
+    pub created_at: Option<DateTime<FixedOffset>>,
+    pub by_bot: bool,
+}
+
+/// Stop conditions for walking a chat's history backwards.
+#[derive(Debug, Clone, Default)]
+pub struct ChatHistoryOptions {
+    /// Stop before yielding the message with this id or any older one.
+    pub until_id: Option<i64>,
+    /// Stop after yielding this many messages.
+    pub limit: Option<usize>,
+}
+
+impl ChatHistoryOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_until_id(mut self, id: i64) -> Self {
+        self.until_id = Some(id);
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct OrderShortcut {
     pub id: OrderId,
     pub description: String,
-    pub price: f64,
-    pub currency: String,
+    pub price: Money,
     pub buyer_username: String,
     pub buyer_id: i64,
     pub chat_id: ChatId,
     pub status: OrderStatus,
     pub date_text: String,
+    /// `date_text` resolved in FunPay's timezone.
+    pub created_at: Option<DateTime<FixedOffset>>,
+    pub subcategory: Subcategory,
+    pub amount: i32,
+}
+
+/// Filters for the sales list. Status, buyer, order id and subcategory are
+/// applied by FunPay; the date range is checked on our side, and since the
+/// list is newest first, paging stops once orders get older than `from`.
+#[derive(Debug, Clone, Default)]
+pub struct OrdersFilter {
+    pub status: Option<OrderStatus>,
+    pub buyer: Option<String>,
+    pub order_id: Option<String>,
+    pub subcategory: Option<(SubcategoryType, i64)>,
+    pub from: Option<DateTime<FixedOffset>>,
+    pub to: Option<DateTime<FixedOffset>>,
+}
+
+impl OrdersFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_status(mut self, status: OrderStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn with_buyer(mut self, buyer: impl Into<String>) -> Self {
+        self.buyer = Some(buyer.into());
+        self
+    }
+
+    pub fn with_order_id(mut self, order_id: impl Into<String>) -> Self {
+        self.order_id = Some(order_id.into());
+        self
+    }
+
+    pub fn with_subcategory(mut self, kind: SubcategoryType, id: i64) -> Self {
+        self.subcategory = Some((kind, id));
+        self
+    }
+
+    pub fn with_from(mut self, from: DateTime<FixedOffset>) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    pub fn with_to(mut self, to: DateTime<FixedOffset>) -> Self {
+        self.to = Some(to);
+        self
+    }
+
+    /// Query parameters understood by `/orders/trade`.
+    pub fn query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+        if let Some(id) = &self.order_id {
+            query.push(("id", id.trim_start_matches('#').to_string()));
+        }
+        if let Some(buyer) = &self.buyer {
+            query.push(("buyer", buyer.clone()));
+        }
+        if let Some(status) = self.status {
+            let state = match status {
+                OrderStatus::Paid => "paid",
+                OrderStatus::Closed => "closed",
+                OrderStatus::Refunded => "refunded",
+            };
+            query.push(("state", state.to_string()));
+        }
+        if let Some((kind, id)) = self.subcategory {
+            let prefix = match kind {
+                SubcategoryType::Common => "lot",
+                SubcategoryType::Currency => "chip",
+            };
+            query.push(("section", format!("{prefix}-{id}")));
+        }
+        query
+    }
+
+    /// Whether `order` falls into the date range. Orders without a parsed
+    /// date are kept.
+    pub fn matches(&self, order: &OrderShortcut) -> bool {
+        let Some(created_at) = order.created_at else {
+            return true;
+        };
+        self.from.is_none_or(|from| created_at >= from) && self.to.is_none_or(|to| created_at <= to)
+    }
+}
+
+/// One page of the sales list. `continuation` loads the next page and is
+/// `None` once the filter cannot match anything further back.
+#[derive(Debug, Clone)]
+pub struct OrdersPage {
+    pub orders: Vec<OrderShortcut>,
+    pub continuation: Option<String>,
+}
+
+/// An order we placed as a buyer, as listed on `/orders/`.
+#[derive(Debug, Clone)]
+pub struct PurchaseShortcut {
+    pub id: OrderId,
+    pub description: String,
+    pub price: Money,
+    pub seller_username: String,
+    pub seller_id: i64,
+    pub chat_id: ChatId,
+    pub status: OrderStatus,
+    pub date_text: String,
+    /// `date_text` resolved in FunPay's timezone.
+    pub created_at: Option<DateTime<FixedOffset>>,
     pub subcategory: Subcategory,
     pub amount: i32,
 }
@@ -264,6 +1305,40 @@ pub struct Review {
     pub reply_by_bot: bool,
 }
 
+/// A page of public reviews from a profile. `continuation` is what
+/// `users/reviews` takes to load the older ones.
+#[derive(Debug, Clone)]
+pub struct ReviewsPage {
+    pub reviews: Vec<Review>,
+    pub continuation: Option<String>,
+}
+
+/// A public profile at `/users/{id}/`.
+#[derive(Debug, Clone)]
+pub struct UserProfile {
+    pub id: i64,
+    pub username: String,
+    /// `None` for the default avatar.
+    pub avatar_url: Option<String>,
+    pub registered_at: Option<DateTime<FixedOffset>>,
+    pub online: bool,
+    /// `Онлайн`, or when the user was last seen.
+    pub status_text: Option<String>,
+    pub rating: Option<f64>,
+    pub reviews_count: u32,
+    pub badges: Vec<String>,
+    pub lots: Vec<UserLotCategory>,
+}
+
+/// A subcategory block on a profile with the user's offers in it.
+#[derive(Debug, Clone)]
+pub struct UserLotCategory {
+    pub node_id: i64,
+    pub name: String,
+    pub kind: SubcategoryType,
+    pub offers: Vec<Offer>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Subcategory {
     pub id: Option<i64>,
@@ -317,8 +1392,8 @@ pub struct Order {
     pub full_description: Option<String>,
     pub subcategory: Option<Subcategory>,
     pub amount: i32,
-    pub sum: f64,
-    pub currency: String,
+    pub sum: Money,
+    pub created_at: Option<DateTime<FixedOffset>>,
     pub buyer_id: i64,
     pub buyer_username: String,
     pub seller_id: i64,
@@ -328,3 +1403,34 @@ pub struct Order {
     pub review: Option<Review>,
     pub order_secrets: Vec<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_price_calculation() {
+        let calc = r#"{
+            "methods": [
+                {"name": "Card", "price": "1 111.11", "unit": "₽", "sort": 1},
+                {"name": "Wallet", "price": "1 052.63", "unit": "₽", "sort": 2}
+            ],
+            "minPrice": "0.35 ₽",
+            "error": false
+        }"#;
+        let mut calc: PriceCalculation = serde_json::from_str(calc).unwrap();
+        calc.price = dec("1000");
+        assert_eq!(calc.buyer_price(), Some(dec("1052.63")));
+        assert_eq!(calc.min_price, Some(dec("0.35")));
+        assert_eq!(calc.commission_percent(), Some(dec("5.26")));
+        assert_eq!(calc.seller_price_for(dec("99")), Some(dec("94.05")));
+
+        let empty: PriceCalculation = serde_json::from_str("{}").unwrap();
+        assert_eq!(empty.seller_price_for(dec("99")), None);
+    }
+}
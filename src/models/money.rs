@@ -0,0 +1,68 @@
+use rust_decimal::Decimal;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Currency {
+    #[default]
+    Rub,
+    Usd,
+    Eur,
+}
+
+impl Currency {
+    /// Recognizes the symbols and codes FunPay prints next to prices.
+    pub fn from_symbol(text: &str) -> Option<Self> {
+        let text = text.trim().trim_end_matches('.').to_lowercase();
+        match text.as_str() {
+            "₽" | "руб" | "р" | "rub" | "rur" => Some(Currency::Rub),
+            "$" | "usd" => Some(Currency::Usd),
+            "€" | "eur" => Some(Currency::Eur),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Currency::Rub => "₽",
+            Currency::Usd => "$",
+            Currency::Eur => "€",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::Rub => "RUB",
+            Currency::Usd => "USD",
+            Currency::Eur => "EUR",
+        }
+    }
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// An exact amount in one of FunPay's currencies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Money {
+    pub amount: Decimal,
+    pub currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: Currency) -> Self {
+        Self { amount, currency }
+    }
+
+    pub fn rub(amount: Decimal) -> Self {
+        Self::new(amount, Currency::Rub)
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.amount, self.currency.symbol())
+    }
+}
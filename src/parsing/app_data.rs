@@ -0,0 +1,36 @@
+use crate::error::FunPayError;
+use crate::models::AppData;
+use scraper::{Html, Selector};
+use serde_json::Value;
+
+pub fn parse_app_data(doc: &Html) -> Result<Option<AppData>, FunPayError> {
+    let sel_body = Selector::parse("body").unwrap();
+    let Some(attr) = doc
+        .select(&sel_body)
+        .next()
+        .and_then(|b| b.value().attr("data-app-data"))
+    else {
+        return Ok(None);
+    };
+
+    let v: Value = serde_json::from_str(attr).map_err(|e| FunPayError::Parse(e.to_string()))?;
+    let user_id = v
+        .get("userId")
+        .and_then(|x| x.as_i64())
+        .ok_or_else(|| FunPayError::Parse(String::from("missing userId")))?;
+    let csrf_token = v
+        .get("csrf-token")
+        .and_then(|x| x.as_str())
+        .ok_or_else(|| FunPayError::Parse(String::from("missing csrf-token")))?
+        .to_string();
+    let locale = v
+        .get("locale")
+        .and_then(|x| x.as_str())
+        .map(|s| s.to_string());
+
+    Ok(Some(AppData {
+        user_id,
+        csrf_token,
+        locale,
+    }))
+}
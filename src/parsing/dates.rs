@@ -0,0 +1,230 @@
+use crate::parsing::locales;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// FunPay renders dates in Moscow time.
+pub const FUNPAY_UTC_OFFSET_SECS: i32 = 3 * 3600;
+
+static RE_NUMERIC: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(\d{1,2})\.(\d{1,2})\.(\d{2,4}),?\s+(\d{1,2}):(\d{2})(?::(\d{2}))?$").unwrap()
+});
+
+static RE_MONTH_NAME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(\d{1,2})\s+([^\s\d,]+)(?:\s+(\d{4}))?,?\s+(\d{1,2}):(\d{2})(?::(\d{2}))?$")
+        .unwrap()
+});
+
+static RE_RELATIVE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?:([^\s\d,]+),?\s+(?:(?:в|о|at)\s+)?)?(\d{1,2}):(\d{2})(?::(\d{2}))?$").unwrap()
+});
+
+static RE_WAIT_UNIT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?:(\d+)\s*)?([^\d\s.,!:;]+)").unwrap());
+
+pub fn funpay_timezone() -> FixedOffset {
+    FixedOffset::east_opt(FUNPAY_UTC_OFFSET_SECS).unwrap()
+}
+
+pub fn funpay_now() -> DateTime<FixedOffset> {
+    Utc::now().with_timezone(&funpay_timezone())
+}
+
+pub fn month_number(name: &str) -> Option<u32> {
+    let name = name.trim_end_matches('.').to_lowercase();
+    locales::MONTHS
+        .iter()
+        .position(|variants| variants.contains(&name.as_str()))
+        .map(|i| i as u32 + 1)
+}
+
+/// Parses dates like `12 марта, 14:32:10`, `3 January 2024, 09:10`,
+/// `12.03.24 14:32`, `Сегодня, 14:32`, `вчора о 09:10` or a bare `14:32`
+/// (today). Dates without a year are resolved to the latest one not in the
+/// future relative to `now`, in `now`'s timezone.
+pub fn parse_datetime(text: &str, now: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    let normalized = text
+        .replace('\u{00A0}', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+
+    if let Some(c) = RE_RELATIVE.captures(&normalized) {
+        let days_ago = match c.get(1).map(|m| m.as_str()) {
+            None => 0,
+            Some(word) if locales::TODAY.contains(&word) => 0,
+            Some(word) if locales::YESTERDAY.contains(&word) => 1,
+            Some(_) => return None,
+        };
+        let date = now.date_naive() - Duration::days(days_ago);
+        let naive = date.and_hms_opt(
+            c[2].parse().ok()?,
+            c[3].parse().ok()?,
+            c.get(4).map_or(Some(0), |m| m.as_str().parse().ok())?,
+        )?;
+        return now.timezone().from_local_datetime(&naive).single();
+    }
+
+    let (day, month, year, hour, minute, second) = if let Some(c) = RE_NUMERIC.captures(&normalized)
+    {
+        let year = c[3].parse::<i32>().ok()?;
+        (
+            c[1].parse::<u32>().ok()?,
+            c[2].parse::<u32>().ok()?,
+            Some(if year < 100 { 2000 + year } else { year }),
+            c[4].parse::<u32>().ok()?,
+            c[5].parse::<u32>().ok()?,
+            c.get(6).map_or(Some(0), |m| m.as_str().parse().ok())?,
+        )
+    } else if let Some(c) = RE_MONTH_NAME.captures(&normalized) {
+        (
+            c[1].parse::<u32>().ok()?,
+            month_number(&c[2])?,
+            c.get(3).and_then(|m| m.as_str().parse::<i32>().ok()),
+            c[4].parse::<u32>().ok()?,
+            c[5].parse::<u32>().ok()?,
+            c.get(6).map_or(Some(0), |m| m.as_str().parse().ok())?,
+        )
+    } else {
+        return None;
+    };
+
+    let build = |year: i32| -> Option<DateTime<FixedOffset>> {
+        let naive: NaiveDateTime =
+            NaiveDate::from_ymd_opt(year, month, day)?.and_hms_opt(hour, minute, second)?;
+        now.timezone().from_local_datetime(&naive).single()
+    };
+
+    match year {
+        Some(year) => build(year),
+        None => {
+            let candidate = build(now.year())?;
+            if candidate > now + Duration::days(1) {
+                build(now.year() - 1)
+            } else {
+                Some(candidate)
+            }
+        }
+    }
+}
+
+/// Parses cooldown messages like `Подождите 3 часа.`, `Please wait 20
+/// minutes` or `Зачекайте годину.` A unit without a number counts as one.
+pub fn parse_wait_duration(text: &str) -> Option<Duration> {
+    let text = text.to_lowercase();
+    let mut total = Duration::zero();
+    let mut found = false;
+    for c in RE_WAIT_UNIT.captures_iter(&text) {
+        let unit = &c[2];
+        let seconds = if locales::matches_any(unit, locales::WAIT_HOURS) {
+            3600
+        } else if locales::matches_any(unit, locales::WAIT_MINUTES) {
+            60
+        } else if locales::matches_any(unit, locales::WAIT_SECONDS) {
+            1
+        } else {
+            continue;
+        };
+        let count = c
+            .get(1)
+            .map_or(Some(1), |m| m.as_str().parse::<i64>().ok())?;
+        total += Duration::seconds(count * seconds);
+        found = true;
+    }
+    found.then_some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference() -> DateTime<FixedOffset> {
+        funpay_timezone()
+            .with_ymd_and_hms(2024, 3, 15, 12, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_datetime_month_names() {
+        let now = reference();
+        let expected = funpay_timezone()
+            .with_ymd_and_hms(2024, 3, 12, 14, 32, 10)
+            .unwrap();
+        assert_eq!(parse_datetime("12 марта, 14:32:10", now), Some(expected));
+        assert_eq!(parse_datetime("12 березня, 14:32:10", now), Some(expected));
+        assert_eq!(parse_datetime("12 March, 14:32:10", now), Some(expected));
+    }
+
+    #[test]
+    fn test_parse_datetime_resolves_year() {
+        let now = reference();
+        let last_year = funpay_timezone()
+            .with_ymd_and_hms(2023, 12, 30, 9, 10, 0)
+            .unwrap();
+        assert_eq!(parse_datetime("30 декабря, 09:10", now), Some(last_year));
+        assert_eq!(
+            parse_datetime("30 декабря 2021, 09:10", now).map(|d| d.year()),
+            Some(2021)
+        );
+        assert_eq!(
+            parse_datetime("01.02.24 08:05", now),
+            Some(
+                funpay_timezone()
+                    .with_ymd_and_hms(2024, 2, 1, 8, 5, 0)
+                    .unwrap()
+            )
+        );
+        assert_eq!(parse_datetime("not a date", now), None);
+    }
+
+    #[test]
+    fn test_parse_datetime_relative_days() {
+        let now = reference();
+        let today = funpay_timezone()
+            .with_ymd_and_hms(2024, 3, 15, 14, 32, 0)
+            .unwrap();
+        let yesterday = funpay_timezone()
+            .with_ymd_and_hms(2024, 3, 14, 9, 10, 0)
+            .unwrap();
+        assert_eq!(parse_datetime("Сегодня, 14:32", now), Some(today));
+        assert_eq!(parse_datetime("today at 14:32", now), Some(today));
+        assert_eq!(parse_datetime("14:32", now), Some(today));
+        assert_eq!(parse_datetime("Вчера, 09:10", now), Some(yesterday));
+        assert_eq!(parse_datetime("вчора о 09:10", now), Some(yesterday));
+        assert_eq!(parse_datetime("Yesterday, 09:10", now), Some(yesterday));
+        assert_eq!(parse_datetime("someday, 09:10", now), None);
+    }
+
+    #[test]
+    fn test_parse_wait_duration() {
+        assert_eq!(
+            parse_wait_duration("Подождите 3 часа."),
+            Some(Duration::hours(3))
+        );
+        assert_eq!(
+            parse_wait_duration("Please wait 20 minutes."),
+            Some(Duration::minutes(20))
+        );
+        assert_eq!(
+            parse_wait_duration("Зачекайте годину."),
+            Some(Duration::hours(1))
+        );
+        assert_eq!(
+            parse_wait_duration("Подождите 1 час 30 минут."),
+            Some(Duration::minutes(90))
+        );
+        assert_eq!(
+            parse_wait_duration("Please wait 1 hour 5 mins."),
+            Some(Duration::minutes(65))
+        );
+        assert_eq!(parse_wait_duration("Предложения подняты"), None);
+        // Words that only start like a unit are not units.
+        assert_eq!(parse_wait_duration("Подождите минимум"), None);
+        assert_eq!(parse_wait_duration("Часто поднимать нельзя"), None);
+        assert_eq!(
+            parse_wait_duration("Минимум: подождите 2 часа."),
+            Some(Duration::hours(2))
+        );
+    }
+}
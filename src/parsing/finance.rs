@@ -0,0 +1,232 @@
+use crate::error::FunPayError;
+use crate::models::enums::{TransactionKind, TransactionStatus};
+use crate::models::ids::OrderId;
+use crate::models::money::Currency;
+use crate::models::{AccountBalance, CurrencyBalance, Transaction, TransactionsPage};
+use crate::parsing::dates::{funpay_now, parse_datetime};
+use crate::parsing::locales;
+use crate::parsing::money::{parse_amount, parse_money};
+use crate::parsing::orders::ensure_logged_in;
+use crate::utils::RE_ORDER_ID;
+use rust_decimal::Decimal;
+use scraper::{Html, Selector};
+
+const CURRENCIES: [Currency; 3] = [Currency::Rub, Currency::Usd, Currency::Eur];
+
+/// Parses the balances on `/account/balance`. The withdrawal form carries
+/// `data-balance-{cur}` (available) and `data-balance-total-{cur}`
+/// attributes; without it the `balances-value` totals are taken as
+/// available.
+pub fn parse_balance(html: &str) -> Result<AccountBalance, FunPayError> {
+    let doc = Html::parse_document(html);
+    ensure_logged_in(&doc)?;
+
+    let mut balances = Vec::new();
+    for currency in CURRENCIES {
+        let code = currency.code().to_lowercase();
+        let sel = Selector::parse(&format!("[data-balance-{code}]")).unwrap();
+        let Some(el) = doc.select(&sel).next() else {
+            continue;
+        };
+        let available = el
+            .value()
+            .attr(&format!("data-balance-{code}"))
+            .and_then(parse_amount)
+            .unwrap_or_default();
+        let total = el
+            .value()
+            .attr(&format!("data-balance-total-{code}"))
+            .and_then(parse_amount)
+            .unwrap_or(available);
+        balances.push(CurrencyBalance {
+            currency,
+            available,
+            frozen: (total - available).max(Decimal::ZERO),
+        });
+    }
+
+    if balances.is_empty() {
+        let sel_value = Selector::parse("span.balances-value").unwrap();
+        for el in doc.select(&sel_value) {
+            if let Some(money) = parse_money(&el.text().collect::<String>()) {
+                balances.push(CurrencyBalance {
+                    currency: money.currency,
+                    available: money.amount,
+                    frozen: Decimal::ZERO,
+                });
+            }
+        }
+    }
+
+    Ok(AccountBalance { balances })
+}
+
+/// Payout methods (`ext_currency_id` values) offered by the withdrawal form.
+pub fn parse_withdrawal_methods(html: &str) -> Vec<String> {
+    let doc = Html::parse_document(html);
+    let sel =
+        Selector::parse("[name=ext_currency_id] option[value], input[name=ext_currency_id][value]")
+            .unwrap();
+    let mut methods: Vec<String> = Vec::new();
+    for el in doc.select(&sel) {
+        let value = el.value().attr("value").unwrap_or("").trim();
+        if !value.is_empty() && !methods.iter().any(|m| m == value) {
+            methods.push(value.to_string());
+        }
+    }
+    methods
+}
+
+/// Parses one page of the balance history. Continuation pages are bare
+/// fragments, so the login check only applies to the first one.
+pub fn parse_transactions_page(
+    html: &str,
+    first_page: bool,
+) -> Result<TransactionsPage, FunPayError> {
+    let doc = Html::parse_document(html);
+    if first_page {
+        ensure_logged_in(&doc)?;
+    }
+
+    let sel_item = Selector::parse(".tc-item[data-transaction]").unwrap();
+    let sel_date = Selector::parse(".tc-date-time").unwrap();
+    let sel_title = Selector::parse(".tc-title").unwrap();
+    let sel_price = Selector::parse(".tc-price").unwrap();
+    let sel_continue = Selector::parse("input[name=continue]").unwrap();
+    let now = funpay_now();
+
+    let mut transactions = Vec::new();
+    for item in doc.select(&sel_item) {
+        let Some(id) = item
+            .value()
+            .attr("data-transaction")
+            .and_then(|s| s.parse::<i64>().ok())
+        else {
+            continue;
+        };
+        let text_of = |sel: &Selector| {
+            item.select(sel)
+                .next()
+                .map(|n| n.text().collect::<String>().trim().to_string())
+                .unwrap_or_default()
+        };
+
+        let title = text_of(&sel_title);
+        let amount = parse_money(&text_of(&sel_price)).unwrap_or_default();
+        let date_text = text_of(&sel_date);
+        let status = item
+            .value()
+            .classes()
+            .find_map(|c| match c {
+                "transaction-status-complete" => Some(TransactionStatus::Complete),
+                "transaction-status-waiting" => Some(TransactionStatus::Waiting),
+                "transaction-status-cancel" => Some(TransactionStatus::Cancelled),
+                _ => None,
+            })
+            .unwrap_or(TransactionStatus::Unknown);
+        let kind = if locales::starts_with_any(&title, locales::TRANSACTION_WITHDRAWAL) {
+            TransactionKind::Withdrawal
+        } else if locales::starts_with_any(&title, locales::TRANSACTION_REFUND) {
+            TransactionKind::Refund
+        } else if locales::starts_with_any(&title, locales::TRANSACTION_DEPOSIT) {
+            TransactionKind::Deposit
+        } else if locales::starts_with_any(&title, locales::TRANSACTION_ORDER) {
+            if amount.amount.is_sign_negative() {
+                TransactionKind::Purchase
+            } else {
+                TransactionKind::Sale
+            }
+        } else {
+            TransactionKind::Other
+        };
+        let order_id = RE_ORDER_ID
+            .find(&title)
+            .map(|m| OrderId::from(m.as_str().trim_start_matches('#')));
+
+        transactions.push(Transaction {
+            id,
+            kind,
+            status,
+            title,
+            amount,
+            order_id,
+            created_at: parse_datetime(&date_text, now),
+            date_text,
+        });
+    }
+
+    let continuation = doc
+        .select(&sel_continue)
+        .next()
+        .and_then(|input| input.value().attr("value"))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    Ok(TransactionsPage {
+        transactions,
+        continuation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::money::Money;
+
+    const BALANCE_HTML: &str = r#"<html><body>
+        <div class="user-link-name">me</div>
+        <span class="balances-value">1 500 ₽</span>
+        <select name="currency_id" data-balance-rub="1 200.50" data-balance-total-rub="1 500.50"
+                data-balance-usd="3" data-balance-total-usd="3"></select>
+        <div class="tc-item transaction-status-complete" data-transaction="901">
+            <span class="tc-date-time">14 марта 2024, 12:30</span>
+            <span class="tc-title">Заказ #ABCD1234</span>
+            <span class="tc-price">+ 1 000,00 ₽</span>
+        </div>
+        <div class="tc-item transaction-status-waiting" data-transaction="900">
+            <span class="tc-date-time">13 марта 2024, 09:00</span>
+            <span class="tc-title">Вывод денег</span>
+            <span class="tc-price">− 500 ₽</span>
+        </div>
+        <select name="ext_currency_id"><option value="">Choose</option><option value="card_rub">Card</option><option value="fps">SBP</option></select>
+        <input type="hidden" name="continue" value="900">
+    </body></html>"#;
+
+    #[test]
+    fn test_parse_balance() {
+        let balance = parse_balance(BALANCE_HTML).unwrap();
+        let rub = balance.get(Currency::Rub).unwrap();
+        assert_eq!(rub.available, Decimal::new(120050, 2));
+        assert_eq!(rub.frozen, Decimal::from(300));
+        assert_eq!(rub.total(), Decimal::new(150050, 2));
+        assert_eq!(
+            balance.get(Currency::Usd).map(|b| b.frozen),
+            Some(Decimal::ZERO)
+        );
+        assert!(balance.get(Currency::Eur).is_none());
+        assert_eq!(
+            parse_withdrawal_methods(BALANCE_HTML),
+            vec!["card_rub", "fps"]
+        );
+    }
+
+    #[test]
+    fn test_parse_transactions_page() {
+        let page = parse_transactions_page(BALANCE_HTML, true).unwrap();
+        assert_eq!(page.continuation.as_deref(), Some("900"));
+        assert_eq!(page.transactions.len(), 2);
+
+        let sale = &page.transactions[0];
+        assert_eq!(sale.id, 901);
+        assert_eq!(sale.kind, TransactionKind::Sale);
+        assert_eq!(sale.status, TransactionStatus::Complete);
+        assert_eq!(sale.amount, Money::rub(Decimal::from(1000)));
+        assert_eq!(sale.order_id, Some(OrderId::from("ABCD1234")));
+        assert!(sale.created_at.is_some());
+
+        let withdrawal = &page.transactions[1];
+        assert_eq!(withdrawal.kind, TransactionKind::Withdrawal);
+        assert_eq!(withdrawal.status, TransactionStatus::Waiting);
+        assert_eq!(withdrawal.amount, Money::rub(Decimal::from(-500)));
+        assert_eq!(withdrawal.order_id, None);
+    }
+}
@@ -1,4 +1,5 @@
 use scraper::{Html, Selector};
+use std::collections::BTreeMap;
 
 pub fn extract_input_value(doc: &Html, name: &str) -> String {
     let selector = Selector::parse(&format!("input[name=\"{}\"]", name))
@@ -45,3 +46,43 @@ pub fn extract_field_value(doc: &Html, name: &str) -> String {
     }
     extract_select_value(doc, name)
 }
+
+/// Collects the values a browser would submit for every control whose name
+/// starts with `prefix`: unchecked checkboxes and radios are left out and a
+/// select without a selected option falls back to its first option.
+pub fn extract_form_fields(doc: &Html, prefix: &str) -> BTreeMap<String, String> {
+    let sel_control = Selector::parse("input[name], select[name], textarea[name]").unwrap();
+    let sel_option = Selector::parse("option").unwrap();
+    let mut out = BTreeMap::new();
+
+    for el in doc.select(&sel_control) {
+        let name = el.value().attr("name").unwrap_or("");
+        if !name.starts_with(prefix) || out.contains_key(name) {
+            continue;
+        }
+        let value = match el.value().name() {
+            "textarea" => el.text().collect::<String>(),
+            "select" => {
+                let options: Vec<_> = el.select(&sel_option).collect();
+                options
+                    .iter()
+                    .find(|o| o.value().attr("selected").is_some())
+                    .or(options.first())
+                    .map(|o| {
+                        o.value()
+                            .attr("value")
+                            .map(str::to_string)
+                            .unwrap_or_else(|| o.text().collect::<String>().trim().to_string())
+                    })
+                    .unwrap_or_default()
+            }
+            _ => match el.value().attr("type").unwrap_or("text") {
+                "checkbox" | "radio" if el.value().attr("checked").is_none() => continue,
+                "checkbox" | "radio" => el.value().attr("value").unwrap_or("on").to_string(),
+                _ => el.value().attr("value").unwrap_or("").to_string(),
+            },
+        };
+        out.insert(name.to_string(), value);
+    }
+    out
+}
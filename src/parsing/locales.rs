@@ -19,6 +19,122 @@ pub const REFUND: &[&str] = &["Возврат", "Повернення", "Refund"
 
 pub const CLOSED: &[&str] = &["Закрыт", "Закрито", "Closed"];
 
+pub const OPENED: &[&str] = &["Открыт", "Відкрито", "Open", "Opened"];
+
+pub const REGISTERED: &[&str] = &["Дата регистрации", "Дата реєстрації", "Registration date"];
+
+pub const ONLINE: &[&str] = &["Онлайн", "Online"];
+
+pub const SYSTEM_ORDER_PAID: &[&str] =
+    &["оплатил заказ", "оплатив замовлення", "has paid for order"];
+
+pub const SYSTEM_ORDER_CONFIRMED: &[&str] = &[
+    "подтвердил успешное выполнение заказа",
+    "підтвердив успішне виконання замовлення",
+    "has confirmed that order",
+];
+
+pub const SYSTEM_REVIEW_LEFT: &[&str] = &[
+    "написал отзыв к заказу",
+    "написав відгук до замовлення",
+    "has given feedback to the order",
+    "has written a review",
+];
+
+pub const SYSTEM_REVIEW_CHANGED: &[&str] = &[
+    "изменил отзыв к заказу",
+    "змінив відгук до замовлення",
+    "has edited their feedback to the order",
+    "has edited their review",
+];
+
+pub const SYSTEM_ORDER_REFUNDED: &[&str] = &[
+    "вернул деньги покупателю",
+    "повернув гроші покупцеві",
+    "has refunded the buyer",
+];
+
+/// Genitive month names as FunPay prints them in dates, January first.
+pub const MONTHS: [&[&str]; 12] = [
+    &["января", "січня", "january", "jan"],
+    &["февраля", "лютого", "february", "feb"],
+    &["марта", "березня", "march", "mar"],
+    &["апреля", "квітня", "april", "apr"],
+    &["мая", "травня", "may"],
+    &["июня", "червня", "june", "jun"],
+    &["июля", "липня", "july", "jul"],
+    &["августа", "серпня", "august", "aug"],
+    &["сентября", "вересня", "september", "sep", "sept"],
+    &["октября", "жовтня", "october", "oct"],
+    &["ноября", "листопада", "november", "nov"],
+    &["декабря", "грудня", "december", "dec"],
+];
+
+/// Titles of `/account/balance` history entries, matched by prefix.
+pub const TRANSACTION_ORDER: &[&str] = &["Заказ", "Замовлення", "Order"];
+
+pub const TRANSACTION_WITHDRAWAL: &[&str] = &["Вывод", "Виведення", "Withdraw"];
+
+pub const TRANSACTION_REFUND: &[&str] = &["Возврат", "Повернення", "Refund"];
+
+pub const TRANSACTION_DEPOSIT: &[&str] = &["Пополнение", "Поповнення", "Deposit", "Top-up"];
+
+/// Relative day words FunPay uses instead of a date for recent events.
+pub const TODAY: &[&str] = &["сегодня", "сьогодні", "today"];
+
+pub const YESTERDAY: &[&str] = &["вчера", "вчора", "yesterday"];
+
+/// Every form of the time units in "wait N hours" style messages; units
+/// are matched as whole words.
+pub const WAIT_SECONDS: &[&str] = &[
+    "сек",
+    "секунда",
+    "секунды",
+    "секунд",
+    "секунду",
+    "секунди",
+    "sec",
+    "secs",
+    "second",
+    "seconds",
+];
+
+pub const WAIT_MINUTES: &[&str] = &[
+    "мин",
+    "минута",
+    "минуты",
+    "минут",
+    "минуту",
+    "хвилина",
+    "хвилини",
+    "хвилин",
+    "хвилину",
+    "min",
+    "mins",
+    "minute",
+    "minutes",
+];
+
+pub const WAIT_HOURS: &[&str] = &[
+    "час",
+    "часа",
+    "часов",
+    "година",
+    "години",
+    "годин",
+    "годину",
+    "hour",
+    "hours",
+];
+
 pub fn matches_any(text: &str, variants: &[&str]) -> bool {
     variants.contains(&text)
 }
+
+pub fn starts_with_any(text: &str, variants: &[&str]) -> bool {
+    variants.iter().any(|v| text.starts_with(v))
+}
+
+pub fn contains_any(text: &str, variants: &[&str]) -> bool {
+    variants.iter().any(|v| text.contains(v))
+}
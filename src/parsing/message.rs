@@ -1,26 +1,220 @@
+use crate::models::ids::{ChatId, OrderId};
+use crate::models::{Message, MessageKind, SystemEvent};
+use crate::parsing::dates::{funpay_now, parse_datetime};
+use crate::parsing::locales;
+use crate::runner::RunnerMessage;
+use crate::utils::{BOT_MARKER, RE_ORDER_ID};
+use chrono::{DateTime, FixedOffset};
 use scraper::{Html, Selector};
 
-pub fn parse_message_html(html: &str) -> (Option<String>, Option<String>) {
+#[derive(Debug, Clone, Default)]
+pub struct ParsedMessage {
+    pub text: Option<String>,
+    pub image_url: Option<String>,
+    pub author_name: Option<String>,
+    pub created_at: Option<DateTime<FixedOffset>>,
+    pub is_system: bool,
+    pub by_bot: bool,
+}
+
+pub fn parse_message_html(html: &str, now: DateTime<FixedOffset>) -> ParsedMessage {
     let html_owned = html.replace("<br>", "\n");
     let doc = Html::parse_fragment(&html_owned);
+    let mut parsed = ParsedMessage::default();
 
+    let sel_author_link = Selector::parse("a.chat-msg-author-link").unwrap();
+    let sel_user_name = Selector::parse("div.media-user-name").unwrap();
+    let sel_date = Selector::parse("div.chat-msg-date").unwrap();
     let sel_text = Selector::parse("div.chat-msg-text").unwrap();
+    let sel_alert = Selector::parse("div[role=alert]").unwrap();
+    let sel_img = Selector::parse("a.chat-img-link").unwrap();
+
+    parsed.author_name = doc
+        .select(&sel_author_link)
+        .next()
+        .map(|n| n.text().collect::<String>())
+        .or_else(|| {
+            doc.select(&sel_user_name).next().map(|n| {
+                n.children()
+                    .filter_map(|c| c.value().as_text().map(|t| t.to_string()))
+                    .collect::<String>()
+            })
+        })
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    parsed.created_at = doc.select(&sel_date).next().and_then(|n| {
+        n.value()
+            .attr("title")
+            .and_then(|title| parse_datetime(title, now))
+    });
+
     if let Some(n) = doc.select(&sel_text).next() {
-        let t = n.text().collect::<String>();
-        return (Some(t), None);
+        parsed.text = Some(n.text().collect::<String>());
+    } else if let Some(n) = doc.select(&sel_alert).next() {
+        parsed.text = Some(n.text().collect::<String>().trim().to_string());
+        parsed.is_system = true;
+    } else if let Some(n) = doc.select(&sel_img).next() {
+        parsed.image_url = n.value().attr("href").map(|s| s.to_string());
     }
 
-    let sel_alert = Selector::parse("div[role=alert]").unwrap();
-    if let Some(n) = doc.select(&sel_alert).next() {
-        let t = n.text().collect::<String>();
-        return (Some(t), None);
+    if let Some(text) = parsed.text.as_mut() {
+        if let Some(stripped) = text.strip_prefix(BOT_MARKER) {
+            *text = stripped.to_string();
+            parsed.by_bot = true;
+        }
     }
 
-    let sel_img = Selector::parse("a.chat-img-link").unwrap();
-    if let Some(n) = doc.select(&sel_img).next() {
-        let href = n.value().attr("href").map(|s| s.to_string());
-        return (None, href);
+    parsed
+}
+
+pub fn parse_system_event(text: &str) -> SystemEvent {
+    let Some(order_id) = RE_ORDER_ID
+        .find(text)
+        .map(|m| OrderId::from(m.as_str().trim_start_matches('#')))
+    else {
+        return SystemEvent::Other;
+    };
+    let lower = text.to_lowercase();
+    if locales::contains_any(&lower, locales::SYSTEM_ORDER_CONFIRMED) {
+        SystemEvent::OrderConfirmed { order_id }
+    } else if locales::contains_any(&lower, locales::SYSTEM_REVIEW_CHANGED) {
+        SystemEvent::ReviewChanged { order_id }
+    } else if locales::contains_any(&lower, locales::SYSTEM_REVIEW_LEFT) {
+        SystemEvent::ReviewLeft { order_id }
+    } else if locales::contains_any(&lower, locales::SYSTEM_ORDER_REFUNDED) {
+        SystemEvent::OrderRefunded { order_id }
+    } else if locales::contains_any(&lower, locales::SYSTEM_ORDER_PAID) {
+        SystemEvent::OrderPaid { order_id }
+    } else {
+        SystemEvent::Other
     }
+}
 
-    (None, None)
+pub fn parse_runner_messages(
+    chat_id: &ChatId,
+    chat_name: Option<&str>,
+    messages: &[RunnerMessage],
+) -> Vec<Message> {
+    let now = funpay_now();
+    messages
+        .iter()
+        .map(|m| {
+            let parsed = parse_message_html(&m.html, now);
+            let kind = if parsed.is_system || m.author == 0 {
+                MessageKind::System {
+                    event: parse_system_event(parsed.text.as_deref().unwrap_or("")),
+                }
+            } else if let Some(url) = parsed.image_url {
+                MessageKind::Image { url }
+            } else {
+                MessageKind::Text
+            };
+            Message {
+                id: m.id,
+                chat_id: chat_id.clone(),
+                chat_name: chat_name.map(|s| s.to_string()),
+                kind,
+                text: parsed.text,
+                interlocutor_id: None,
+                author_id: m.author,
+                author_name: parsed.author_name,
+                created_at: parsed.created_at,
+                by_bot: parsed.by_bot,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, Timelike};
+
+    #[test]
+    fn test_parse_message_kinds() {
+        let messages = [
+            RunnerMessage {
+                id: 1,
+                author: 42,
+                html: r#"<div class="chat-msg-item chat-msg-with-head"><div class="media-user-name"><a href="https://funpay.com/users/42/" class="chat-msg-author-link">buyer</a><div class="chat-msg-date" title="12 марта, 14:32:10">14:32</div></div><div class="chat-msg-body"><div class="chat-msg-text">I paid</div></div></div>"#.to_string(),
+            },
+            RunnerMessage {
+                id: 2,
+                author: 0,
+                html: r#"<div class="chat-msg-item chat-msg-with-head"><div class="media-user-name">FunPay <span class="chat-msg-author-label label label-primary">notification</span></div><div class="chat-msg-body"><div class="alert alert-with-icon alert-info" role="alert">The buyer buyer has paid for order #ABCD1234.</div></div></div>"#.to_string(),
+            },
+            RunnerMessage {
+                id: 3,
+                author: 7,
+                html: r#"<div class="chat-msg-item"><div class="chat-msg-body"><a class="chat-img-link" href="https://sfunpay.com/s/chat/img.jpg"><img src="x"></a></div></div>"#.to_string(),
+            },
+            RunnerMessage {
+                id: 4,
+                author: 7,
+                html: "<div class=\"chat-msg-item\"><div class=\"chat-msg-text\">\u{2064}auto reply</div></div>".to_string(),
+            },
+        ];
+
+        let parsed = parse_runner_messages(&ChatId::from("users-7-42"), None, &messages);
+
+        assert_eq!(parsed[0].kind, MessageKind::Text);
+        assert_eq!(parsed[0].author_name.as_deref(), Some("buyer"));
+        assert_eq!(
+            parsed[0]
+                .created_at
+                .map(|d| (d.month(), d.day(), d.hour(), d.minute())),
+            Some((3, 12, 14, 32))
+        );
+        assert!(!parsed[0].by_bot);
+
+        assert_eq!(
+            parsed[1].kind,
+            MessageKind::System {
+                event: SystemEvent::OrderPaid {
+                    order_id: OrderId::from("ABCD1234")
+                }
+            }
+        );
+        assert_eq!(parsed[1].author_name.as_deref(), Some("FunPay"));
+
+        assert_eq!(
+            parsed[2].kind,
+            MessageKind::Image {
+                url: "https://sfunpay.com/s/chat/img.jpg".to_string()
+            }
+        );
+        assert!(parsed[2].text.is_none());
+
+        assert!(parsed[3].by_bot);
+        assert_eq!(parsed[3].text.as_deref(), Some("auto reply"));
+    }
+
+    #[test]
+    fn test_parse_system_event_locales() {
+        let cases = [
+            (
+                "Покупатель buyer подтвердил успешное выполнение заказа #ABCD1234 и отправил деньги продавцу seller.",
+                SystemEvent::OrderConfirmed {
+                    order_id: OrderId::from("ABCD1234"),
+                },
+            ),
+            (
+                "Покупець buyer змінив відгук до замовлення #ABCD1234.",
+                SystemEvent::ReviewChanged {
+                    order_id: OrderId::from("ABCD1234"),
+                },
+            ),
+            (
+                "The seller seller has refunded the buyer buyer on order #ABCD1234.",
+                SystemEvent::OrderRefunded {
+                    order_id: OrderId::from("ABCD1234"),
+                },
+            ),
+            ("Добро пожаловать!", SystemEvent::Other),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_system_event(text), expected, "{text}");
+        }
+    }
 }
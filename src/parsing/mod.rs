@@ -1,17 +1,29 @@
+mod app_data;
 mod category;
+pub mod dates;
+mod finance;
 mod forms;
 pub mod locales;
 mod message;
+pub mod money;
 pub mod offers;
 mod orders;
+mod users;
 
+pub use app_data::parse_app_data;
 pub use category::{parse_category_filters, parse_category_subcategories};
+pub use finance::{parse_balance, parse_transactions_page, parse_withdrawal_methods};
 pub use forms::{
-    extract_checkbox_value, extract_field_value, extract_input_value, extract_select_value,
-    extract_textarea_value,
+    extract_checkbox_value, extract_field_value, extract_form_fields, extract_input_value,
+    extract_select_value, extract_textarea_value,
 };
-pub use message::parse_message_html;
+pub use message::{parse_message_html, parse_runner_messages, ParsedMessage};
 pub use offers::{
     parse_market_offers, parse_my_offers, parse_offer_edit_params, parse_offer_full_params,
+    parse_raise_game_id, parse_raise_modal_nodes, parse_user_lot_nodes,
 };
-pub use orders::{parse_order_page, parse_order_secrets, parse_orders_list};
+pub use orders::{
+    parse_order_page, parse_order_secrets, parse_orders_list, parse_orders_page,
+    parse_purchases_list,
+};
+pub use users::{parse_user_profile, parse_user_reviews};
@@ -0,0 +1,100 @@
+use crate::models::money::{Currency, Money};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// Parses a number as FunPay prints it: spaces, NBSP or thin spaces as
+/// thousand separators and either `.` or `,` as the decimal separator.
+pub fn parse_amount(text: &str) -> Option<Decimal> {
+    let digits: String = text
+        .trim()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .take_while(|c| c.is_ascii_digit() || matches!(c, '.' | ','))
+        .collect();
+    if digits.is_empty() {
+        return None;
+    }
+    Decimal::from_str(&digits.replace(',', ".")).ok()
+}
+
+/// Finds the first price in `text`, e.g. `1 234.50 ₽`, `1 234,50 ₽`, `$12.5`
+/// or `Сумма −99 руб.`. The currency is taken from the token right after the
+/// number, then from the one right before it, and defaults to rubles.
+pub fn parse_money(text: &str) -> Option<Money> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let (prefix, rest) = text.split_at(start);
+    let end = rest
+        .find(|c: char| !(c.is_ascii_digit() || c.is_whitespace() || matches!(c, '.' | ',')))
+        .unwrap_or(rest.len());
+    let (number, suffix) = rest.split_at(end);
+
+    let mut amount = parse_amount(number)?;
+    let prefix = prefix.trim_end();
+    let negative = prefix.ends_with(['-', '−']);
+    if negative {
+        amount.set_sign_negative(true);
+    }
+    let prefix = prefix.trim_end_matches(['-', '−', '+']).trim_end();
+
+    let currency = suffix
+        .split_whitespace()
+        .next()
+        .and_then(Currency::from_symbol)
+        .or_else(|| {
+            prefix
+                .split_whitespace()
+                .next_back()
+                .and_then(Currency::from_symbol)
+        })
+        .unwrap_or_default();
+    Some(Money::new(amount, currency))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_money_separators() {
+        assert_eq!(parse_money("1 234.50 ₽"), Some(Money::rub(dec("1234.50"))));
+        assert_eq!(
+            parse_money("1\u{00A0}234,50\u{00A0}₽"),
+            Some(Money::rub(dec("1234.50")))
+        );
+        assert_eq!(
+            parse_money("12\u{2009}345\u{2009}678.9 ₽"),
+            Some(Money::rub(dec("12345678.9")))
+        );
+        assert_eq!(
+            parse_money("3\u{202F}000,05 руб."),
+            Some(Money::rub(dec("3000.05")))
+        );
+    }
+
+    #[test]
+    fn test_parse_money_currencies() {
+        assert_eq!(
+            parse_money("12.5 $"),
+            Some(Money::new(dec("12.5"), Currency::Usd))
+        );
+        assert_eq!(
+            parse_money("$12.5"),
+            Some(Money::new(dec("12.5"), Currency::Usd))
+        );
+        assert_eq!(
+            parse_money("0,99 €"),
+            Some(Money::new(dec("0.99"), Currency::Eur))
+        );
+        assert_eq!(parse_money("150"), Some(Money::rub(dec("150"))));
+        assert_eq!(
+            parse_money("Сумма −99,90 руб."),
+            Some(Money::rub(dec("-99.90")))
+        );
+        assert_eq!(parse_money("₽"), None);
+        assert_eq!(parse_amount(""), None);
+    }
+}
@@ -1,12 +1,16 @@
+use crate::models::is_builtin_offer_field;
+use crate::models::money::{Currency, Money};
 use crate::models::{
     MarketOffer, Offer, OfferCustomField, OfferEditParams, OfferFieldOption, OfferFieldType,
     OfferFullParams,
 };
+use crate::parsing::money::{parse_amount, parse_money};
 use crate::parsing::{
-    extract_checkbox_value, extract_field_value, extract_input_value, extract_textarea_value,
+    extract_checkbox_value, extract_field_value, extract_form_fields, extract_input_value,
+    extract_textarea_value,
 };
 use regex::Regex;
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html, Selector};
 
 pub fn parse_my_offers(html: &str, node_id: i64) -> Vec<Offer> {
     let doc = Html::parse_document(html);
@@ -34,16 +38,11 @@ pub fn parse_my_offers(html: &str, node_id: i64) -> Vec<Offer> {
             .map(|el| el.text().collect::<String>().trim().to_string())
             .unwrap_or_default();
 
-        let price_el = item.select(&sel_price).next();
-        let price = price_el
-            .and_then(|el| el.value().attr("data-s"))
-            .and_then(|s| s.parse::<f64>().ok())
-            .unwrap_or(0.0);
-
-        let currency = price_el
-            .and_then(|el| el.select(&sel_unit).next())
-            .map(|el| el.text().collect::<String>().trim().to_string())
-            .unwrap_or_else(|| "₽".to_string());
+        let price = item
+            .select(&sel_price)
+            .next()
+            .map(|el| parse_offer_price(el, &sel_unit))
+            .unwrap_or_default();
 
         let active = !item.value().classes().any(|c| c == "warning");
 
@@ -52,7 +51,6 @@ pub fn parse_my_offers(html: &str, node_id: i64) -> Vec<Offer> {
             node_id,
             description,
             price,
-            currency,
             active,
         });
     }
@@ -96,16 +94,11 @@ pub fn parse_market_offers(html: &str, node_id: i64) -> Vec<MarketOffer> {
             .map(|el| el.text().collect::<String>().trim().to_string())
             .unwrap_or_default();
 
-        let price_el = item.select(&sel_price).next();
-        let price = price_el
-            .and_then(|el| el.value().attr("data-s"))
-            .and_then(|s| s.parse::<f64>().ok())
-            .unwrap_or(0.0);
-
-        let currency = price_el
-            .and_then(|el| el.select(&sel_unit).next())
-            .map(|el| el.text().collect::<String>().trim().to_string())
-            .unwrap_or_else(|| "₽".to_string());
+        let price = item
+            .select(&sel_price)
+            .next()
+            .map(|el| parse_offer_price(el, &sel_unit))
+            .unwrap_or_default();
 
         let seller_el = item.select(&sel_seller).next();
         let seller_name = seller_el
@@ -165,7 +158,6 @@ pub fn parse_market_offers(html: &str, node_id: i64) -> Vec<MarketOffer> {
             node_id,
             description,
             price,
-            currency,
             seller_id,
             seller_name,
             seller_online,
@@ -178,6 +170,24 @@ pub fn parse_market_offers(html: &str, node_id: i64) -> Vec<MarketOffer> {
     offers
 }
 
+/// Price of a `div.tc-price`: the exact amount from `data-s` when present,
+/// the visible text otherwise, in the currency of its `span.unit`.
+pub(crate) fn parse_offer_price(el: ElementRef, sel_unit: &Selector) -> Money {
+    let text = el.text().collect::<String>();
+    let shown = parse_money(&text).unwrap_or_default();
+    let currency = el
+        .select(sel_unit)
+        .next()
+        .and_then(|u| Currency::from_symbol(&u.text().collect::<String>()))
+        .unwrap_or(shown.currency);
+    let amount = el
+        .value()
+        .attr("data-s")
+        .and_then(parse_amount)
+        .unwrap_or(shown.amount);
+    Money::new(amount, currency)
+}
+
 pub fn parse_offer_edit_params(html: &str) -> OfferEditParams {
     let doc = Html::parse_document(html);
 
@@ -200,6 +210,11 @@ pub fn parse_offer_edit_params(html: &str) -> OfferEditParams {
         active: Some(extract_checkbox_value(&doc, "active")),
         location: Some(extract_input_value(&doc, "location")),
         deleted: None,
+        extra_fields: extract_form_fields(&doc, "fields[")
+            .into_iter()
+            .filter(|(name, _)| !is_builtin_offer_field(name))
+            .collect(),
+        removed_fields: Default::default(),
     }
 }
 
@@ -246,11 +261,21 @@ pub fn parse_offer_full_params(html: &str, offer_id: i64, node_id: i64) -> Offer
                 _ => OfferFieldType::Text,
             };
 
+            // Checkboxes read like `extract_form_fields`: the submitted value
+            // when checked, empty when not. The value is kept as the only option.
+            let mut options = vec![];
             let actual_value = if field_type == OfferFieldType::Checkbox {
-                if input.value().attr("checked").is_some() {
-                    "true".to_string()
+                let checked = input.value().attr("checked").is_some();
+                let on_value = input.value().attr("value").unwrap_or("on").to_string();
+                options.push(OfferFieldOption {
+                    value: on_value.clone(),
+                    label: label_text.clone(),
+                    selected: checked,
+                });
+                if checked {
+                    on_value
                 } else {
-                    "false".to_string()
+                    String::new()
                 }
             } else {
                 value
@@ -261,7 +286,8 @@ pub fn parse_offer_full_params(html: &str, offer_id: i64, node_id: i64) -> Offer
                 label: label_text.clone(),
                 field_type,
                 value: actual_value,
-                options: vec![],
+                options,
+                required: input.value().attr("required").is_some(),
             });
         } else if let Some(textarea) = group.select(&sel_textarea).next() {
             let name = textarea.value().attr("name").unwrap_or("");
@@ -280,6 +306,7 @@ pub fn parse_offer_full_params(html: &str, offer_id: i64, node_id: i64) -> Offer
                 field_type: OfferFieldType::Textarea,
                 value,
                 options: vec![],
+                required: textarea.value().attr("required").is_some(),
             });
         } else if let Some(select) = group.select(&sel_select).next() {
             let name = select.value().attr("name").unwrap_or("");
@@ -312,6 +339,7 @@ pub fn parse_offer_full_params(html: &str, offer_id: i64, node_id: i64) -> Offer
                 field_type: OfferFieldType::Select,
                 value: selected_value,
                 options,
+                required: select.value().attr("required").is_some(),
             });
         }
     }
@@ -339,9 +367,53 @@ pub fn parse_offer_full_params(html: &str, offer_id: i64, node_id: i64) -> Offer
     }
 }
 
+/// Game id from the "raise offers" button on a `lots/{node}/trade` page.
+pub fn parse_raise_game_id(html: &str) -> Option<i64> {
+    let doc = Html::parse_document(html);
+    let sel_raise = Selector::parse(".js-lot-raise[data-game]").unwrap();
+    doc.select(&sel_raise)
+        .next()
+        .and_then(|el| el.value().attr("data-game"))
+        .and_then(|s| s.parse::<i64>().ok())
+}
+
+/// Subcategory ids offered in the `lots/raise` modal.
+pub fn parse_raise_modal_nodes(html: &str) -> Vec<i64> {
+    let doc = Html::parse_fragment(html);
+    let sel_node = Selector::parse("input[type=\"checkbox\"][value]").unwrap();
+    doc.select(&sel_node)
+        .filter_map(|el| el.value().attr("value"))
+        .filter_map(|s| s.parse::<i64>().ok())
+        .collect()
+}
+
+/// Lot subcategories listed on a user's profile page, i.e. the ones the user
+/// has active offers in. Currency (`chips`) sections are skipped.
+pub fn parse_user_lot_nodes(html: &str) -> Vec<i64> {
+    let doc = Html::parse_document(html);
+    let sel_title = Selector::parse("div.offer-list-title a[href]").unwrap();
+    let re_node = Regex::new(r"/lots/(\d+)/?").unwrap();
+    let mut nodes = Vec::new();
+    for a in doc.select(&sel_title) {
+        let node = re_node
+            .captures(a.value().attr("href").unwrap_or(""))
+            .and_then(|c| c[1].parse::<i64>().ok());
+        if let Some(node) = node {
+            if !nodes.contains(&node) {
+                nodes.push(node);
+            }
+        }
+    }
+    nodes
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::FunPayError;
+    use crate::models::{OfferChangeSet, OfferSaveResult};
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
 
     const TEST_HTML: &str = r#"
 <a href="https://funpay.com/lots/offer?id=58789647" class="tc-item offer-promo offer-promoted" data-online="1" data-user="4029757" data-f-quantity="13 звёзд" data-f-method="подарком">
@@ -422,8 +494,8 @@ mod tests {
         assert_eq!(first.id, 58789647);
         assert_eq!(first.node_id, 2418);
         assert_eq!(first.description, "13 звёзд, Подарком");
-        assert!((first.price - 20.89613).abs() < 0.0001);
-        assert_eq!(first.currency, "₽");
+        assert_eq!(first.price.amount, Decimal::from_str("20.89613").unwrap());
+        assert_eq!(first.price.currency, Currency::Rub);
         assert_eq!(first.seller_id, 4029757);
         assert_eq!(first.seller_name, "Ded777Veka");
         assert!(first.seller_online);
@@ -433,8 +505,8 @@ mod tests {
         let second = &offers[1];
         assert_eq!(second.id, 58821247);
         assert_eq!(second.description, "13 звёзд, Подарком");
-        assert!((second.price - 19.038697).abs() < 0.0001);
-        assert_eq!(second.currency, "₽");
+        assert_eq!(second.price.amount, Decimal::from_str("19.038697").unwrap());
+        assert_eq!(second.price.currency, Currency::Rub);
         assert_eq!(second.seller_id, 17151546);
         assert_eq!(second.seller_name, "Ksannyaa");
         assert!(second.seller_online);
@@ -444,8 +516,8 @@ mod tests {
         let third = &offers[2];
         assert_eq!(third.id, 51391953);
         assert_eq!(third.description, "13 звёзд, Подарком");
-        assert!((third.price - 19.154786).abs() < 0.0001);
-        assert_eq!(third.currency, "₽");
+        assert_eq!(third.price.amount, Decimal::from_str("19.154786").unwrap());
+        assert_eq!(third.price.currency, Currency::Rub);
         assert_eq!(third.seller_id, 16023197);
         assert_eq!(third.seller_name, "starsTGgreat");
         assert!(third.seller_online);
@@ -481,4 +553,195 @@ mod tests {
         assert_eq!(offers[0].id, 123);
         assert_eq!(offers[0].node_id, 1234);
     }
+
+    const OFFER_FORM_HTML: &str = r#"
+<form>
+<div class="form-group lot-field">
+<label class="control-label">Region</label>
+<select name="fields[region]" class="form-control" required>
+<option value="">Select</option>
+<option value="eu">Europe</option>
+<option value="na" selected>North America</option>
+</select>
+</div>
+<div class="form-group lot-field">
+<label class="control-label">With mail</label>
+<input type="checkbox" name="fields[mail]">
+</div>
+<div class="form-group lot-field">
+<label class="control-label">Summary</label>
+<input type="text" name="fields[summary][ru]" value="">
+</div>
+<input type="text" name="price" value="">
+</form>
+"#;
+
+    #[test]
+    fn test_validate_offer_fields() {
+        let form = parse_offer_full_params(OFFER_FORM_HTML, 0, 1234);
+        let region = form
+            .custom_fields
+            .iter()
+            .find(|f| f.name == "fields[region]")
+            .unwrap();
+        assert!(region.required);
+        assert_eq!(region.options.len(), 3);
+
+        let valid = OfferEditParams {
+            summary_ru: Some("Account".to_string()),
+            ..OfferEditParams::new()
+                .with_field("region", "eu")
+                .with_field("mail", "on")
+        };
+        assert!(form.validate_fields(&valid.form_fields()).is_ok());
+
+        let invalid = OfferEditParams::new()
+            .with_field("region", "asia")
+            .with_field("mail", "yes")
+            .with_field("platform", "pc");
+        let errors = form.validate_fields(&invalid.form_fields()).unwrap_err();
+        assert!(errors["fields[region]"].contains("unknown option"));
+        assert!(errors.contains_key("fields[mail]"));
+        assert_eq!(errors["fields[platform]"], "unknown field");
+
+        // A submitted checkbox is always checked, so "false" must not pass.
+        for unchecked in ["false", "0"] {
+            let params = OfferEditParams::new()
+                .with_field("region", "eu")
+                .with_field("mail", unchecked);
+            let errors = form.validate_fields(&params.form_fields()).unwrap_err();
+            assert!(errors["fields[mail]"].contains("remove the field"));
+        }
+        let mail = form
+            .custom_fields
+            .iter()
+            .find(|f| f.name == "fields[mail]")
+            .unwrap();
+        assert_eq!(mail.value, "");
+        assert_eq!(mail.options[0].value, "on");
+        assert!(form.validate_fields(&form.field_values()).is_ok());
+
+        let errors = form
+            .validate_fields(&OfferEditParams::new().form_fields())
+            .unwrap_err();
+        assert_eq!(errors["fields[region]"], "required field is missing");
+    }
+
+    #[test]
+    fn test_offer_save_result() {
+        let done: OfferSaveResult = serde_json::from_str(
+            r#"{"done": true, "url": "https://funpay.com/lots/offer?id=777", "errors": []}"#,
+        )
+        .unwrap();
+        assert_eq!(done.offer_id(), Some(777));
+        assert!(done.into_result().is_ok());
+
+        let rejected: OfferSaveResult = serde_json::from_str(
+            r#"{"error": "Проверьте правильность заполнения формы", "errors": {"fields[region]": "Неверное значение", "price": ["Слишком низкая цена"]}}"#,
+        )
+        .unwrap();
+        match rejected.into_result() {
+            Err(FunPayError::OfferValidation { field_errors }) => {
+                assert_eq!(field_errors["fields[region]"], "Неверное значение");
+                assert_eq!(field_errors["price"], "Слишком низкая цена");
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        let general: OfferSaveResult =
+            serde_json::from_str(r#"{"error": true, "msg": "Лот не найден"}"#).unwrap();
+        match general.into_result() {
+            Err(FunPayError::OfferValidation { field_errors }) => {
+                assert_eq!(field_errors["error"], "Лот не найден");
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_edit_params_keep_custom_fields() {
+        let current = parse_offer_edit_params(OFFER_FORM_HTML);
+        assert_eq!(current.extra_fields.get("fields[region]").unwrap(), "na");
+        assert!(!current.extra_fields.contains_key("fields[mail]"));
+        assert!(!current.extra_fields.contains_key("fields[summary][ru]"));
+
+        let merged = current.merge(OfferEditParams::new().with_price("99.5"));
+        assert_eq!(merged.price.as_deref(), Some("99.5"));
+        assert_eq!(merged.extra_fields.get("fields[region]").unwrap(), "na");
+
+        let merged = merged.merge(OfferEditParams::new().with_field("mail", "on"));
+        assert_eq!(merged.extra_fields.get("fields[region]").unwrap(), "na");
+        assert_eq!(merged.extra_fields.get("fields[mail]").unwrap(), "on");
+    }
+
+    #[test]
+    fn test_offer_change_set() {
+        let current = parse_offer_edit_params(OFFER_FORM_HTML)
+            .merge(OfferEditParams::new().with_price("99.5"));
+        let updated = current.clone().merge(
+            OfferEditParams::new()
+                .with_field("mail", "on")
+                .with_price("120"),
+        );
+
+        let changes = OfferChangeSet::between(&current, &updated);
+        assert_eq!(changes.changes.len(), 2);
+        let price = changes.get("price").unwrap();
+        assert_eq!(price.old.as_deref(), Some("99.5"));
+        assert_eq!(price.new.as_deref(), Some("120"));
+        let mail = changes.get("fields[mail]").unwrap();
+        assert_eq!(
+            (mail.old.as_deref(), mail.new.as_deref()),
+            (None, Some("on"))
+        );
+        assert!(OfferChangeSet::between(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_edit_params_remove_fields() {
+        let form = parse_offer_full_params(OFFER_FORM_HTML, 0, 1234);
+        let current = parse_offer_edit_params(OFFER_FORM_HTML)
+            .merge(OfferEditParams::new().with_field("mail", "on"));
+        assert_eq!(current.extra_fields.get("fields[mail]").unwrap(), "on");
+
+        let update = OfferEditParams::new()
+            .without_field("mail")
+            .without_field("fields[summary][ru]");
+        assert!(form.validate(&update).is_ok());
+        let merged = current.merge(update);
+        assert!(!merged.extra_fields.contains_key("fields[mail]"));
+        assert_eq!(merged.summary_ru, None);
+        assert_eq!(merged.extra_fields.get("fields[region]").unwrap(), "na");
+        assert!(merged.removed_fields.is_empty());
+
+        // Removing a required field fails validation; setting it again
+        // cancels the removal.
+        let update = OfferEditParams::new().without_field("region");
+        assert!(form.validate(&update).is_err());
+        let update = update.with_field("region", "eu");
+        assert!(update.removed_fields.is_empty());
+        assert!(form.validate(&update).is_ok());
+    }
+
+    #[test]
+    fn test_parse_raise_pages() {
+        let trade = r#"<div class="page-content">
+            <button class="btn btn-default js-lot-raise" data-game="41" data-node="210">Поднять предложения</button>
+        </div>"#;
+        assert_eq!(parse_raise_game_id(trade), Some(41));
+        assert_eq!(parse_raise_game_id("<div></div>"), None);
+
+        let modal = r#"<div class="modal-body">
+            <div class="checkbox"><label><input type="checkbox" value="210" checked> Accounts</label></div>
+            <div class="checkbox"><label><input type="checkbox" value="211"> Items</label></div>
+        </div>"#;
+        assert_eq!(parse_raise_modal_nodes(modal), vec![210, 211]);
+
+        let profile = r#"<div class="offer-list-title-container">
+            <div class="offer-list-title"><h3><a href="https://funpay.com/lots/210/">Accounts</a></h3></div>
+            <div class="offer-list-title"><h3><a href="https://funpay.com/chips/99/">Gold</a></h3></div>
+            <div class="offer-list-title"><h3><a href="https://funpay.com/lots/211/">Items</a></h3></div>
+        </div>"#;
+        assert_eq!(parse_user_lot_nodes(profile), vec![210, 211]);
+    }
 }
@@ -1,20 +1,72 @@
 use crate::error::FunPayError;
 use crate::models::enums::OrderStatus;
 use crate::models::ids::{ChatId, OrderId};
-use crate::models::{Order, OrderShortcut, Review, Subcategory};
+use crate::models::{Order, OrderShortcut, OrdersPage, PurchaseShortcut, Review, Subcategory};
+use crate::parsing::dates::{funpay_now, parse_datetime};
 use crate::parsing::locales;
+use crate::parsing::money::parse_money;
+use crate::utils::BOT_MARKER;
 use regex::Regex;
 use scraper::{Html, Selector};
 use std::collections::HashMap;
 
+/// Parses the sales list at `/orders/trade`.
 pub fn parse_orders_list(html: &str, my_id: i64) -> Result<Vec<OrderShortcut>, FunPayError> {
     let doc = Html::parse_document(html);
+    ensure_logged_in(&doc)?;
+    Ok(parse_order_rows(&doc, my_id))
+}
 
-    let sel_user = Selector::parse("div.user-link-name").unwrap();
-    if doc.select(&sel_user).next().is_none() {
-        return Err(FunPayError::Unauthorized);
+/// Parses one page of the sales list. Continuation pages are bare table
+/// fragments without the page header, so the login check only applies to the
+/// first one.
+pub fn parse_orders_page(
+    html: &str,
+    my_id: i64,
+    first_page: bool,
+) -> Result<OrdersPage, FunPayError> {
+    let doc = Html::parse_document(html);
+    if first_page {
+        ensure_logged_in(&doc)?;
     }
+    let sel_continue = Selector::parse("input[name=continue]").unwrap();
+    let continuation = doc
+        .select(&sel_continue)
+        .next()
+        .and_then(|input| input.value().attr("value"))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    Ok(OrdersPage {
+        orders: parse_order_rows(&doc, my_id),
+        continuation,
+    })
+}
+
+/// Parses the purchases list at `/orders/`. Its rows are laid out like the
+/// sales list, with the seller where the buyer would be.
+pub fn parse_purchases_list(html: &str, my_id: i64) -> Result<Vec<PurchaseShortcut>, FunPayError> {
+    let doc = Html::parse_document(html);
+    ensure_logged_in(&doc)?;
+    Ok(parse_order_rows(&doc, my_id)
+        .into_iter()
+        .map(|o| PurchaseShortcut {
+            id: o.id,
+            description: o.description,
+            price: o.price,
+            seller_username: o.buyer_username,
+            seller_id: o.buyer_id,
+            chat_id: o.chat_id,
+            status: o.status,
+            date_text: o.date_text,
+            created_at: o.created_at,
+            subcategory: o.subcategory,
+            amount: o.amount,
+        })
+        .collect())
+}
 
+/// Order list rows with the other party in the `buyer_*` fields.
+fn parse_order_rows(doc: &Html, my_id: i64) -> Vec<OrderShortcut> {
     let sel_item = Selector::parse("a.tc-item").unwrap();
     let sel_order = Selector::parse("div.tc-order").unwrap();
     let sel_desc = Selector::parse("div.order-desc").unwrap();
@@ -29,6 +81,7 @@ pub fn parse_orders_list(html: &str, my_id: i64) -> Result<Vec<OrderShortcut>, F
         Regex::new(r"/(?:chips|lots|market|goods|game|category|subcategory)/(\d+)/?").unwrap();
     let re_amount = Regex::new(r"(?i)(\d+)\s*(шт|pcs|pieces|ед)\.?").unwrap();
 
+    let now = funpay_now();
     let mut out = Vec::new();
 
     for a in doc.select(&sel_item) {
@@ -66,15 +119,7 @@ pub fn parse_orders_list(html: &str, my_id: i64) -> Result<Vec<OrderShortcut>, F
             .next()
             .map(|n| n.text().collect::<String>())
             .unwrap_or_default();
-        let price_text = price_text_raw.replace('\u{00A0}', " ").trim().to_string();
-
-        let (price_val, currency) = if let Some((p, cur)) = price_text.rsplit_once(' ') {
-            let pv = p.replace(' ', "");
-            let parsed = pv.parse::<f64>().unwrap_or(0.0);
-            (parsed, cur.to_string())
-        } else {
-            (0.0, String::new())
-        };
+        let price = parse_money(&price_text_raw).unwrap_or_default();
 
         let buyer_span = a.select(&sel_buyer).next();
         let buyer_username = buyer_span
@@ -118,6 +163,7 @@ pub fn parse_orders_list(html: &str, my_id: i64) -> Result<Vec<OrderShortcut>, F
             .next()
             .map(|n| n.text().collect::<String>().trim().to_string())
             .unwrap_or_default();
+        let created_at = parse_datetime(&date_text, now);
 
         let amount = re_amount
             .captures(&description)
@@ -130,19 +176,27 @@ pub fn parse_orders_list(html: &str, my_id: i64) -> Result<Vec<OrderShortcut>, F
         out.push(OrderShortcut {
             id: OrderId::from(id),
             description,
-            price: price_val,
-            currency,
+            price,
             buyer_username,
             buyer_id,
             chat_id,
             status,
             date_text,
+            created_at,
             subcategory,
             amount,
         });
     }
 
-    Ok(out)
+    out
+}
+
+pub(crate) fn ensure_logged_in(doc: &Html) -> Result<(), FunPayError> {
+    let sel_user = Selector::parse("div.user-link-name").unwrap();
+    if doc.select(&sel_user).next().is_none() {
+        return Err(FunPayError::Unauthorized);
+    }
+    Ok(())
 }
 
 pub fn parse_order_secrets(doc: &Html) -> Vec<String> {
@@ -215,6 +269,7 @@ pub fn parse_order_page(html: &str, order_id: &str) -> Result<Order, FunPayError
     let mut lot_params: Vec<(String, String)> = Vec::new();
     let buyer_params: HashMap<String, String> = HashMap::new();
     let mut amount: Option<i32> = None;
+    let mut created_at = None;
     let mut subcategory: Option<Subcategory> = None;
 
     for p in doc.select(&sel_param) {
@@ -255,6 +310,13 @@ pub fn parse_order_page(html: &str, order_id: &str) -> Result<Order, FunPayError
                     amount = Some(a);
                 }
             }
+        } else if locales::matches_any(h, locales::OPENED) {
+            let now = funpay_now();
+            created_at = p
+                .select(&sel_div)
+                .next()
+                .and_then(|c| c.text().map(str::trim).find(|t| !t.is_empty()))
+                .and_then(|t| parse_datetime(t, now));
         } else if !locales::matches_any(h, locales::PAID_PRODUCT) {
             let content_div = p.select(&sel_div).next();
             if let Some(content) = content_div {
@@ -288,27 +350,9 @@ pub fn parse_order_page(html: &str, order_id: &str) -> Result<Order, FunPayError
         })
         .unwrap_or((None, None));
 
-    let (sum_val, currency) = if let Some(sum) = sum_info {
-        let sum_text = sum.text().collect::<String>();
-        let re = Regex::new(r"([\d.,]+)\s*([A-Za-zА-Яа-я₽$€£¥₴]+)").unwrap();
-        if let Some(captures) = re.captures(&sum_text) {
-            let amount_str = captures.get(1).map(|m| m.as_str()).unwrap_or("");
-            let curr_str = captures.get(2).map(|m| m.as_str()).unwrap_or("");
-            let amount_parsed = amount_str.replace(',', ".").parse::<f64>().ok();
-            (
-                amount_parsed,
-                if curr_str.is_empty() {
-                    None
-                } else {
-                    Some(curr_str.to_string())
-                },
-            )
-        } else {
-            (None, None)
-        }
-    } else {
-        (None, None)
-    };
+    let sum = sum_info
+        .and_then(|sum| parse_money(&sum.text().collect::<String>()))
+        .unwrap_or_default();
 
     let chat_id = {
         let sel_chat = Selector::parse("a[href*='/chat/']").unwrap();
@@ -333,10 +377,18 @@ pub fn parse_order_page(html: &str, order_id: &str) -> Result<Order, FunPayError
                 .select(&text_sel)
                 .next()
                 .map(|t| t.text().collect::<String>().trim().to_string());
+            let reply_sel = Selector::parse(".review-item-answer").unwrap();
+            let reply = r
+                .select(&reply_sel)
+                .next()
+                .map(|t| t.text().collect::<String>().trim().to_string())
+                .filter(|t| !t.is_empty());
+            let reply_by_bot = reply.as_deref().is_some_and(|t| t.starts_with(BOT_MARKER));
+            let reply = reply.map(|t| t.trim_start_matches(BOT_MARKER).to_string());
             Review {
                 stars,
                 text,
-                reply: None,
+                reply,
                 anonymous: false,
                 html: r.text().collect::<String>(),
                 hidden: false,
@@ -344,7 +396,7 @@ pub fn parse_order_page(html: &str, order_id: &str) -> Result<Order, FunPayError
                 author: None,
                 author_id: None,
                 by_bot: false,
-                reply_by_bot: false,
+                reply_by_bot,
             }
         })
     };
@@ -358,8 +410,8 @@ pub fn parse_order_page(html: &str, order_id: &str) -> Result<Order, FunPayError
         full_description,
         subcategory,
         amount: amount.unwrap_or(0),
-        sum: sum_val.unwrap_or(0.0),
-        currency: currency.unwrap_or_else(|| String::from("RUB")),
+        sum,
+        created_at,
         buyer_id: buyer_id.unwrap_or(0),
         buyer_username: buyer_username.unwrap_or_default(),
         seller_id: 0,
@@ -370,3 +422,89 @@ pub fn parse_order_page(html: &str, order_id: &str) -> Result<Order, FunPayError
         order_secrets,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::enums::SubcategoryType;
+    use crate::models::money::Money;
+    use crate::models::OrdersFilter;
+    use crate::parsing::dates::funpay_timezone;
+    use chrono::TimeZone;
+    use rust_decimal::Decimal;
+
+    const PURCHASES_HTML: &str = r#"<html><body>
+        <div class="user-link-name">me</div>
+        <a class="tc-item info" href="https://funpay.com/orders/ABCD1234/">
+            <div class="tc-date"><div class="tc-date-time">Сегодня, 14:32</div></div>
+            <div class="tc-order">#ABCD1234</div>
+            <div class="order-desc">
+                <div>Gold, 5 шт.</div>
+                <div class="text-muted">WoW, Gold</div>
+            </div>
+            <div class="tc-user">
+                <div class="media-user-name"><span data-href="https://funpay.com/users/42/">seller</span></div>
+            </div>
+            <div class="tc-price">1 250.50 <span class="unit">₽</span></div>
+        </a>
+    </body></html>"#;
+
+    #[test]
+    fn test_parse_purchases_list() {
+        let list = parse_purchases_list(PURCHASES_HTML, 7).unwrap();
+        assert_eq!(list.len(), 1);
+        let p = &list[0];
+        assert_eq!(p.id, OrderId::from("ABCD1234".to_string()));
+        assert_eq!(p.seller_username, "seller");
+        assert_eq!(p.seller_id, 42);
+        assert_eq!(p.chat_id, ChatId::from("users-7-42".to_string()));
+        assert_eq!(p.status, OrderStatus::Paid);
+        assert_eq!(p.price, Money::rub(Decimal::new(125050, 2)));
+        assert_eq!(p.amount, 5);
+        assert_eq!(p.subcategory.name, "WoW, Gold");
+        assert!(p.created_at.is_some());
+    }
+
+    #[test]
+    fn test_parse_orders_page_and_filter() {
+        let fragment = PURCHASES_HTML
+            .replace(r#"<div class="user-link-name">me</div>"#, "")
+            .replace(
+                "</body>",
+                r#"<form class="dyn-table-form"><input type="hidden" name="continue" value="tok 1"></form></body>"#,
+            );
+        assert!(matches!(
+            parse_orders_page(&fragment, 7, true),
+            Err(FunPayError::Unauthorized)
+        ));
+        let page = parse_orders_page(&fragment, 7, false).unwrap();
+        assert_eq!(page.orders.len(), 1);
+        assert_eq!(page.continuation.as_deref(), Some("tok 1"));
+
+        let filter = OrdersFilter::new()
+            .with_status(OrderStatus::Closed)
+            .with_buyer("bob")
+            .with_order_id("#ABCD1234")
+            .with_subcategory(SubcategoryType::Currency, 2);
+        assert_eq!(
+            filter.query(),
+            vec![
+                ("id", "ABCD1234".to_string()),
+                ("buyer", "bob".to_string()),
+                ("state", "closed".to_string()),
+                ("section", "chip-2".to_string()),
+            ]
+        );
+
+        let mut order = page.orders[0].clone();
+        let day = |d| {
+            funpay_timezone()
+                .with_ymd_and_hms(2024, 3, d, 0, 0, 0)
+                .unwrap()
+        };
+        order.created_at = Some(day(10));
+        assert!(OrdersFilter::new().with_from(day(1)).matches(&order));
+        assert!(!OrdersFilter::new().with_from(day(11)).matches(&order));
+        assert!(!OrdersFilter::new().with_to(day(9)).matches(&order));
+    }
+}
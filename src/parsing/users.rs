@@ -0,0 +1,329 @@
+use crate::error::FunPayError;
+use crate::models::enums::SubcategoryType;
+use crate::models::ids::OrderId;
+use crate::models::{Offer, Review, ReviewsPage, UserLotCategory, UserProfile};
+use crate::parsing::dates::{funpay_now, parse_datetime};
+use crate::parsing::locales;
+use crate::parsing::offers::parse_offer_price;
+use crate::utils::BOT_MARKER;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+
+/// Parses a public profile page at `/users/{id}/`.
+pub fn parse_user_profile(html: &str, user_id: i64) -> Result<UserProfile, FunPayError> {
+    let doc = Html::parse_document(html);
+
+    let sel_username = Selector::parse("div.profile h1 span.mr4").unwrap();
+    let username = doc
+        .select(&sel_username)
+        .next()
+        .map(|n| n.text().collect::<String>().trim().to_string())
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| FunPayError::Parse(format!("user {user_id} profile has no username")))?;
+
+    let sel_avatar = Selector::parse("div.profile div.avatar-photo").unwrap();
+    let re_url = Regex::new(r#"url\(["']?([^"')]+)["']?\)"#).unwrap();
+    let avatar_url = doc
+        .select(&sel_avatar)
+        .next()
+        .and_then(|n| n.value().attr("style"))
+        .and_then(|style| re_url.captures(style))
+        .map(|c| c[1].to_string())
+        .filter(|url| !url.contains("/img/layout/avatar.png"));
+
+    let sel_status = Selector::parse("div.profile span.media-user-status").unwrap();
+    let status_text = doc
+        .select(&sel_status)
+        .next()
+        .map(|n| n.text().collect::<String>().trim().to_string())
+        .filter(|s| !s.is_empty());
+    let online = status_text
+        .as_deref()
+        .is_some_and(|s| locales::matches_any(s, locales::ONLINE));
+
+    let sel_badge = Selector::parse("div.profile h1 span.label").unwrap();
+    let badges = doc
+        .select(&sel_badge)
+        .map(|n| n.text().collect::<String>().trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let sel_param = Selector::parse("div.param-item").unwrap();
+    let sel_h5 = Selector::parse("h5").unwrap();
+    let sel_div = Selector::parse("div").unwrap();
+    let now = funpay_now();
+    let registered_at = doc
+        .select(&sel_param)
+        .find(|p| {
+            p.select(&sel_h5).next().is_some_and(|h| {
+                locales::matches_any(h.text().collect::<String>().trim(), locales::REGISTERED)
+            })
+        })
+        .and_then(|p| p.select(&sel_div).next())
+        .and_then(|c| c.text().map(str::trim).find(|t| !t.is_empty()))
+        .and_then(|t| parse_datetime(t, now));
+
+    let sel_rating = Selector::parse("div.rating-value span.big").unwrap();
+    let rating = doc.select(&sel_rating).next().and_then(|n| {
+        n.text()
+            .collect::<String>()
+            .trim()
+            .replace(',', ".")
+            .parse()
+            .ok()
+    });
+
+    let sel_count = Selector::parse("div.rating-full-count").unwrap();
+    let re_digits = Regex::new(r"\d+").unwrap();
+    let reviews_count = doc
+        .select(&sel_count)
+        .next()
+        .map(|n| n.text().collect::<String>().replace([' ', '\u{00A0}'], ""))
+        .and_then(|t| re_digits.find(&t).and_then(|m| m.as_str().parse().ok()))
+        .unwrap_or(0);
+
+    Ok(UserProfile {
+        id: user_id,
+        username,
+        avatar_url,
+        registered_at,
+        online,
+        status_text,
+        rating,
+        reviews_count,
+        badges,
+        lots: parse_profile_lots(&doc),
+    })
+}
+
+fn parse_profile_lots(doc: &Html) -> Vec<UserLotCategory> {
+    let sel_block = Selector::parse("div.offer").unwrap();
+    let sel_title = Selector::parse("div.offer-list-title a[href]").unwrap();
+    let sel_item = Selector::parse("a.tc-item").unwrap();
+    let sel_desc = Selector::parse("div.tc-desc-text").unwrap();
+    let sel_price = Selector::parse("div.tc-price").unwrap();
+    let sel_unit = Selector::parse("span.unit").unwrap();
+    let re_node = Regex::new(r"/(lots|chips)/(\d+)/?").unwrap();
+    let re_offer_id = Regex::new(r"[?&]id=(\d+)").unwrap();
+
+    let mut categories = Vec::new();
+    for block in doc.select(&sel_block) {
+        let Some(title) = block.select(&sel_title).next() else {
+            continue;
+        };
+        let Some(c) = re_node.captures(title.value().attr("href").unwrap_or("")) else {
+            continue;
+        };
+        let Ok(node_id) = c[2].parse::<i64>() else {
+            continue;
+        };
+        let kind = if &c[1] == "chips" {
+            SubcategoryType::Currency
+        } else {
+            SubcategoryType::Common
+        };
+
+        let offers = block
+            .select(&sel_item)
+            .filter_map(|item| {
+                let id = re_offer_id
+                    .captures(item.value().attr("href").unwrap_or(""))
+                    .and_then(|c| c[1].parse::<i64>().ok())?;
+                let description = item
+                    .select(&sel_desc)
+                    .next()
+                    .map(|el| el.text().collect::<String>().trim().to_string())
+                    .unwrap_or_default();
+                let price = item
+                    .select(&sel_price)
+                    .next()
+                    .map(|el| parse_offer_price(el, &sel_unit))
+                    .unwrap_or_default();
+                Some(Offer {
+                    id,
+                    node_id,
+                    description,
+                    price,
+                    active: true,
+                })
+            })
+            .collect();
+
+        categories.push(UserLotCategory {
+            node_id,
+            name: title.text().collect::<String>().trim().to_string(),
+            kind,
+            offers,
+        });
+    }
+    categories
+}
+
+/// Parses the public reviews on a profile page, or a `users/reviews`
+/// continuation fragment.
+pub fn parse_user_reviews(html: &str) -> ReviewsPage {
+    let doc = Html::parse_document(html);
+    let sel_review = Selector::parse("div.review-container").unwrap();
+    let sel_continue = Selector::parse("input[name=continue]").unwrap();
+
+    let reviews = doc.select(&sel_review).map(parse_review_item).collect();
+    let continuation = doc
+        .select(&sel_continue)
+        .next()
+        .and_then(|input| input.value().attr("value"))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    ReviewsPage {
+        reviews,
+        continuation,
+    }
+}
+
+fn parse_review_item(el: ElementRef) -> Review {
+    let sel_author = Selector::parse("div.review-item-user div.media-user-name").unwrap();
+    let sel_author_link = Selector::parse("div.review-item-user a[href*='/users/']").unwrap();
+    let sel_rating = Selector::parse("div.review-item-rating div.rating div").unwrap();
+    let sel_text = Selector::parse("div.review-item-text").unwrap();
+    let sel_reply = Selector::parse("div.review-item-answer").unwrap();
+    let sel_order = Selector::parse("div.review-item-order a[href*='/orders/']").unwrap();
+    let re_user = Regex::new(r"/users/(\d+)/").unwrap();
+    let re_order = Regex::new(r"/orders/([A-Z0-9]+)/").unwrap();
+    let re_stars = Regex::new(r"^rating(\d)$").unwrap();
+
+    let text_of = |sel: &Selector| {
+        el.select(sel)
+            .next()
+            .map(|n| n.text().collect::<String>().trim().to_string())
+            .filter(|t| !t.is_empty())
+    };
+
+    let author_id = el
+        .select(&sel_author_link)
+        .next()
+        .and_then(|a| a.value().attr("href"))
+        .and_then(|href| re_user.captures(href))
+        .and_then(|c| c[1].parse::<i64>().ok());
+    let stars = el.select(&sel_rating).next().and_then(|r| {
+        r.value()
+            .classes()
+            .find_map(|class| re_stars.captures(class))
+            .and_then(|c| c[1].parse::<i32>().ok())
+    });
+    let order_id = el
+        .select(&sel_order)
+        .next()
+        .and_then(|a| a.value().attr("href"))
+        .and_then(|href| re_order.captures(href))
+        .map(|c| OrderId::from(&c[1]));
+
+    let text = text_of(&sel_text);
+    let reply = text_of(&sel_reply);
+    let by_bot = text.as_deref().is_some_and(|t| t.starts_with(BOT_MARKER));
+    let reply_by_bot = reply.as_deref().is_some_and(|t| t.starts_with(BOT_MARKER));
+
+    Review {
+        stars,
+        text: text.map(|t| t.trim_start_matches(BOT_MARKER).to_string()),
+        reply: reply.map(|t| t.trim_start_matches(BOT_MARKER).to_string()),
+        anonymous: author_id.is_none(),
+        html: el.html(),
+        hidden: el.value().classes().any(|c| c == "review-hidden"),
+        order_id,
+        author: text_of(&sel_author),
+        author_id,
+        by_bot,
+        reply_by_bot,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    const PROFILE_HTML: &str = r#"<html><body>
+        <div class="profile">
+            <div class="profile-header">
+                <div class="profile-photo">
+                    <div class="avatar-photo" style="background-image: url(https://sfunpay.com/s/avatar/ab/cd/abcd.jpg);"></div>
+                </div>
+                <h1 class="mb40"><span class="mr4">SellerOne</span><span class="label label-success">Verified</span></h1>
+                <span class="media-user-status">Онлайн</span>
+            </div>
+            <div class="param-item"><h5>Дата регистрации</h5><div>12 марта 2020, 13:14<br>4 года назад</div></div>
+            <div class="rating-value"><span class="big">4.9</span></div>
+            <div class="rating-full-count">Всего 1 234 отзыва</div>
+            <div class="offer">
+                <div class="offer-list-title"><h3><a href="https://funpay.com/lots/210/">Accounts</a></h3></div>
+                <div class="tc">
+                    <a class="tc-item" href="https://funpay.com/lots/offer?id=555">
+                        <div class="tc-desc-text">Account lvl 80</div>
+                        <div class="tc-price" data-s="149.99">149.99 <span class="unit">₽</span></div>
+                    </a>
+                </div>
+            </div>
+            <div class="offer">
+                <div class="offer-list-title"><h3><a href="https://funpay.com/chips/2/">Gold</a></h3></div>
+            </div>
+        </div>
+        <div class="review-container">
+            <div class="review-item-user"><a href="https://funpay.com/users/42/"><div class="media-user-name">bob</div></a></div>
+            <div class="review-item-order"><a href="https://funpay.com/orders/ABCD1234/">Order</a></div>
+            <div class="review-item-rating"><div class="rating"><div class="rating5"></div></div></div>
+            <div class="review-item-text">Fast and polite</div>
+            <div class="review-item-answer">&#x2064;Thanks!</div>
+        </div>
+        <div class="review-container">
+            <div class="review-item-user"><div class="media-user-name">Покупатель</div></div>
+            <div class="review-item-rating"><div class="rating"><div class="rating2"></div></div></div>
+            <div class="review-item-text">Slow</div>
+        </div>
+        <input type="hidden" name="continue" value="=>42:9000">
+    </body></html>"#;
+
+    #[test]
+    fn test_parse_user_profile() {
+        let profile = parse_user_profile(PROFILE_HTML, 7).unwrap();
+        assert_eq!(profile.username, "SellerOne");
+        assert_eq!(
+            profile.avatar_url.as_deref(),
+            Some("https://sfunpay.com/s/avatar/ab/cd/abcd.jpg")
+        );
+        assert!(profile.online);
+        assert_eq!(profile.badges, vec!["Verified".to_string()]);
+        assert_eq!(profile.rating, Some(4.9));
+        assert_eq!(profile.reviews_count, 1234);
+        assert_eq!(
+            profile.registered_at.map(|d| d.to_rfc3339()).as_deref(),
+            Some("2020-03-12T13:14:00+03:00")
+        );
+        assert_eq!(profile.lots.len(), 2);
+        assert_eq!(profile.lots[0].node_id, 210);
+        assert_eq!(profile.lots[0].offers[0].id, 555);
+        assert_eq!(
+            profile.lots[0].offers[0].price.amount,
+            Decimal::new(14999, 2)
+        );
+        assert_eq!(profile.lots[1].kind, SubcategoryType::Currency);
+    }
+
+    #[test]
+    fn test_parse_user_reviews() {
+        let page = parse_user_reviews(PROFILE_HTML);
+        assert_eq!(page.continuation.as_deref(), Some("=>42:9000"));
+        assert_eq!(page.reviews.len(), 2);
+
+        let first = &page.reviews[0];
+        assert_eq!(first.stars, Some(5));
+        assert_eq!(first.author.as_deref(), Some("bob"));
+        assert_eq!(first.author_id, Some(42));
+        assert_eq!(first.order_id, Some(OrderId::from("ABCD1234")));
+        assert_eq!(first.reply.as_deref(), Some("Thanks!"));
+        assert!(first.reply_by_bot);
+
+        let second = &page.reviews[1];
+        assert_eq!(second.stars, Some(2));
+        assert!(second.anonymous);
+        assert_eq!(second.text.as_deref(), Some("Slow"));
+    }
+}
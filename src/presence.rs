@@ -0,0 +1,112 @@
+use crate::parsing::dates::funpay_timezone;
+use chrono::{DateTime, FixedOffset, NaiveTime, TimeZone};
+use std::time::Duration;
+
+/// A daily time range; `end` before `start` wraps past midnight, so
+/// 09:00–02:00 covers the evening and the early night.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresenceWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl PresenceWindow {
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end }
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Settings for `FunPayAccount::spawn_presence_keeper`.
+#[derive(Debug, Clone)]
+pub struct PresenceOptions {
+    /// How often the `c-p-u` runner object is sent.
+    pub interval: Duration,
+    /// How often the home page is loaded as well.
+    pub home_interval: Duration,
+    /// When to appear online; always when empty.
+    pub windows: Vec<PresenceWindow>,
+    /// Timezone the windows are in; FunPay's (Moscow) by default.
+    pub timezone: FixedOffset,
+}
+
+impl Default for PresenceOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            home_interval: Duration::from_secs(5 * 60),
+            windows: Vec::new(),
+            timezone: funpay_timezone(),
+        }
+    }
+}
+
+impl PresenceOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_home_interval(mut self, interval: Duration) -> Self {
+        self.home_interval = interval;
+        self
+    }
+
+    pub fn with_window(mut self, start: NaiveTime, end: NaiveTime) -> Self {
+        self.windows.push(PresenceWindow::new(start, end));
+        self
+    }
+
+    pub fn with_timezone(mut self, timezone: FixedOffset) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn is_active_at<Tz: TimeZone>(&self, at: &DateTime<Tz>) -> bool {
+        let time = at.with_timezone(&self.timezone).time();
+        self.windows.is_empty() || self.windows.iter().any(|w| w.contains(time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_presence_windows() {
+        let options = PresenceOptions::new().with_window(time(9, 0), time(2, 0));
+        let at = |h, m| {
+            funpay_timezone()
+                .with_ymd_and_hms(2024, 3, 15, h, m, 0)
+                .unwrap()
+        };
+        assert!(options.is_active_at(&at(9, 0)));
+        assert!(options.is_active_at(&at(23, 59)));
+        assert!(options.is_active_at(&at(1, 30)));
+        assert!(!options.is_active_at(&at(2, 0)));
+        assert!(!options.is_active_at(&at(5, 0)));
+
+        let utc = chrono::Utc.with_ymd_and_hms(2024, 3, 15, 3, 0, 0).unwrap();
+        assert!(!options.is_active_at(&utc));
+        assert!(PresenceOptions::new().is_active_at(&utc));
+
+        let daytime = PresenceOptions::new().with_window(time(10, 0), time(18, 0));
+        assert!(daytime.is_active_at(&at(12, 0)));
+        assert!(!daytime.is_active_at(&at(20, 0)));
+    }
+}
@@ -0,0 +1,273 @@
+use crate::client::account::FunPaySender;
+use crate::error::FunPayError;
+use crate::events::Event;
+use crate::models::money::Money;
+use crate::models::{MarketOffer, OfferEditParams};
+use rust_decimal::Decimal;
+use std::time::Duration;
+use tokio::sync::broadcast::Sender;
+use tokio::time::sleep;
+
+/// Pricing rule for our offers in one node. Competitor prices, `undercut`,
+/// `floor` and `ceiling` are all buyer-facing prices; the target is converted
+/// to the seller price through `FunPaySender::seller_price_for` before it is
+/// saved.
+#[derive(Debug, Clone)]
+pub struct RepriceRule {
+    pub node_id: i64,
+    /// How much cheaper than the lowest competitor we want to be.
+    pub undercut: Decimal,
+    pub floor: Option<Decimal>,
+    pub ceiling: Option<Decimal>,
+    /// Ignore sellers rated below this (and unrated sellers) when set.
+    pub min_seller_rating: Option<f64>,
+    /// Only compare against sellers that are online right now.
+    pub online_only: bool,
+    /// Offers to reprice; all of ours in the node when `None`.
+    pub offer_ids: Option<Vec<i64>>,
+}
+
+impl RepriceRule {
+    pub fn new(node_id: i64) -> Self {
+        Self {
+            node_id,
+            undercut: Decimal::ZERO,
+            floor: None,
+            ceiling: None,
+            min_seller_rating: None,
+            online_only: false,
+            offer_ids: None,
+        }
+    }
+
+    pub fn with_undercut(mut self, undercut: Decimal) -> Self {
+        self.undercut = undercut;
+        self
+    }
+
+    pub fn with_floor(mut self, floor: Decimal) -> Self {
+        self.floor = Some(floor);
+        self
+    }
+
+    pub fn with_ceiling(mut self, ceiling: Decimal) -> Self {
+        self.ceiling = Some(ceiling);
+        self
+    }
+
+    pub fn with_min_seller_rating(mut self, rating: f64) -> Self {
+        self.min_seller_rating = Some(rating);
+        self
+    }
+
+    pub fn with_online_only(mut self, online_only: bool) -> Self {
+        self.online_only = online_only;
+        self
+    }
+
+    pub fn with_offer_ids(mut self, offer_ids: Vec<i64>) -> Self {
+        self.offer_ids = Some(offer_ids);
+        self
+    }
+
+    /// Lowest price among the competitors this rule looks at.
+    pub fn lowest_competitor_price(&self, offers: &[MarketOffer], own_id: i64) -> Option<Decimal> {
+        offers
+            .iter()
+            .filter(|o| o.seller_id != own_id && !o.is_promo)
+            .filter(|o| !self.online_only || o.seller_online)
+            .filter(|o| {
+                self.min_seller_rating
+                    .is_none_or(|min| o.seller_rating.is_some_and(|r| r >= min))
+            })
+            .map(|o| o.price.amount)
+            .filter(|p| *p > Decimal::ZERO)
+            .min()
+    }
+
+    /// Buyer-facing price we want, or `None` when there is nothing to follow.
+    pub fn target_price(&self, offers: &[MarketOffer], own_id: i64) -> Option<Decimal> {
+        let target = match self.lowest_competitor_price(offers, own_id) {
+            Some(lowest) => lowest - self.undercut,
+            None => self.ceiling?,
+        };
+        let target = self.floor.map_or(target, |floor| target.max(floor));
+        Some(self.ceiling.map_or(target, |ceiling| target.min(ceiling)))
+    }
+}
+
+/// A price change applied (or, in dry-run mode, computed) by the repricer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepriceChange {
+    pub offer_id: i64,
+    pub node_id: i64,
+    /// Seller prices before and after.
+    pub old_price: Money,
+    pub new_price: Money,
+    /// Buyer-facing price the new seller price was derived from.
+    pub target_price: Decimal,
+    pub competitor_price: Option<Decimal>,
+    pub applied: bool,
+}
+
+/// Periodically moves our offers' prices according to per-node rules. Every
+/// change is sent as `Event::OfferRepriced`.
+pub struct Repricer {
+    sender: FunPaySender,
+    events_tx: Sender<Event>,
+    rules: Vec<RepriceRule>,
+    interval: Duration,
+    dry_run: bool,
+}
+
+impl Repricer {
+    pub fn new(sender: FunPaySender, events_tx: Sender<Event>) -> Self {
+        Self {
+            sender,
+            events_tx,
+            rules: Vec::new(),
+            interval: Duration::from_secs(300),
+            dry_run: false,
+        }
+    }
+
+    pub fn with_rule(mut self, rule: RepriceRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Compute and report changes without saving them.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub async fn run(self) {
+        loop {
+            for rule in &self.rules {
+                if let Err(e) = self.apply_rule(rule).await {
+                    log::error!(target: "funpay_client", "Repricing node {} failed: {e}", rule.node_id);
+                }
+            }
+            sleep(self.interval).await;
+        }
+    }
+
+    /// Applies every rule once and returns the changes made.
+    pub async fn run_once(&self) -> Result<Vec<RepriceChange>, FunPayError> {
+        let mut changes = Vec::new();
+        for rule in &self.rules {
+            changes.extend(self.apply_rule(rule).await?);
+        }
+        Ok(changes)
+    }
+
+    async fn apply_rule(&self, rule: &RepriceRule) -> Result<Vec<RepriceChange>, FunPayError> {
+        let market = self.sender.get_market_offers(rule.node_id).await?;
+        let competitor_price = rule.lowest_competitor_price(&market, self.sender.seller_id());
+        let Some(target_price) = rule.target_price(&market, self.sender.seller_id()) else {
+            log::debug!(target: "funpay_client", "No competitors to follow in node {}", rule.node_id);
+            return Ok(Vec::new());
+        };
+
+        let new_price = self
+            .sender
+            .seller_price_for(rule.node_id, target_price)
+            .await?;
+
+        let mut changes = Vec::new();
+        for offer in self.sender.get_my_offers(rule.node_id).await? {
+            if rule
+                .offer_ids
+                .as_ref()
+                .is_some_and(|ids| !ids.contains(&offer.id))
+            {
+                continue;
+            }
+            if offer.price.amount == new_price {
+                continue;
+            }
+
+            if !self.dry_run {
+                let params = OfferEditParams::new().with_price(format!("{new_price:.2}"));
+                if let Err(e) = self.sender.edit_offer(offer.id, rule.node_id, params).await {
+                    log::error!(target: "funpay_client", "Failed to reprice offer {}: {e}", offer.id);
+                    continue;
+                }
+            }
+            let change = RepriceChange {
+                offer_id: offer.id,
+                node_id: rule.node_id,
+                old_price: offer.price,
+                new_price: Money::new(new_price, offer.price.currency),
+                target_price,
+                competitor_price,
+                applied: !self.dry_run,
+            };
+            let _ = self.events_tx.send(Event::OfferRepriced {
+                change: change.clone(),
+            });
+            changes.push(change);
+        }
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn offer(seller_id: i64, price: i64, online: bool, rating: Option<f64>) -> MarketOffer {
+        MarketOffer {
+            id: seller_id * 10,
+            node_id: 1,
+            description: String::new(),
+            price: Money::rub(Decimal::from(price)),
+            seller_id,
+            seller_name: String::new(),
+            seller_online: online,
+            seller_rating: rating,
+            seller_reviews: 0,
+            is_promo: false,
+        }
+    }
+
+    #[test]
+    fn test_target_price() {
+        let mut promo = offer(5, 50, true, Some(5.0));
+        promo.is_promo = true;
+        let market = vec![
+            offer(1, 120, true, Some(4.9)),
+            offer(2, 100, false, Some(5.0)),
+            offer(3, 90, true, Some(3.0)),
+            offer(99, 80, true, Some(5.0)),
+            promo,
+        ];
+
+        let rule = RepriceRule::new(1).with_undercut(dec("0.5"));
+        assert_eq!(rule.target_price(&market, 99), Some(dec("89.5")));
+
+        let rule = rule.with_min_seller_rating(4.5);
+        assert_eq!(rule.target_price(&market, 99), Some(dec("99.5")));
+
+        let rule = rule.with_online_only(true).with_ceiling(dec("110"));
+        assert_eq!(rule.target_price(&market, 99), Some(dec("110")));
+
+        let rule = RepriceRule::new(1)
+            .with_undercut(Decimal::ONE)
+            .with_floor(dec("95"));
+        assert_eq!(rule.target_price(&market, 99), Some(dec("95")));
+
+        assert_eq!(RepriceRule::new(1).target_price(&[], 99), None);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::models::Order;
+use std::collections::BTreeMap;
+
+/// What to do about a review, as decided by [`ReviewTemplates`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReviewReplyAction {
+    Reply(String),
+    /// Rated below the escalation threshold; left for a human.
+    Escalate,
+    /// No review, already answered, or no template for its rating.
+    Skip,
+}
+
+/// Reply templates keyed by star rating. Templates may use `{buyer}`,
+/// `{order_id}`, `{stars}` and `{description}`.
+#[derive(Debug, Clone, Default)]
+pub struct ReviewTemplates {
+    templates: BTreeMap<i32, String>,
+    escalate_below: Option<i32>,
+}
+
+impl ReviewTemplates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_reply(mut self, stars: i32, template: impl Into<String>) -> Self {
+        self.templates.insert(stars, template.into());
+        self
+    }
+
+    /// Escalate instead of replying to reviews rated below `stars`.
+    pub fn with_escalate_below(mut self, stars: i32) -> Self {
+        self.escalate_below = Some(stars);
+        self
+    }
+
+    pub fn action_for(&self, order: &Order) -> ReviewReplyAction {
+        let Some(review) = &order.review else {
+            return ReviewReplyAction::Skip;
+        };
+        let Some(stars) = review.stars.filter(|s| *s > 0) else {
+            return ReviewReplyAction::Skip;
+        };
+        if review.reply.is_some() {
+            return ReviewReplyAction::Skip;
+        }
+        if self.escalate_below.is_some_and(|min| stars < min) {
+            return ReviewReplyAction::Escalate;
+        }
+        match self.templates.get(&stars) {
+            Some(template) => ReviewReplyAction::Reply(render(template, order, stars)),
+            None => ReviewReplyAction::Skip,
+        }
+    }
+}
+
+fn render(template: &str, order: &Order, stars: i32) -> String {
+    template
+        .replace("{buyer}", &order.buyer_username)
+        .replace("{order_id}", &order.id)
+        .replace("{stars}", &stars.to_string())
+        .replace(
+            "{description}",
+            order.short_description.as_deref().unwrap_or_default(),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::enums::OrderStatus;
+    use crate::models::ids::{ChatId, OrderId};
+    use crate::models::Review;
+    use std::collections::HashMap;
+
+    fn order(stars: Option<i32>, reply: Option<&str>) -> Order {
+        Order {
+            id: OrderId::from("ABCD1234"),
+            status: OrderStatus::Closed,
+            lot_params: Vec::new(),
+            buyer_params: HashMap::new(),
+            short_description: Some("Gold".to_string()),
+            full_description: None,
+            subcategory: None,
+            amount: 1,
+            sum: Default::default(),
+            created_at: None,
+            buyer_id: 42,
+            buyer_username: "bob".to_string(),
+            seller_id: 0,
+            seller_username: String::new(),
+            chat_id: ChatId::from("users-7-42"),
+            html: String::new(),
+            review: stars.map(|stars| Review {
+                stars: Some(stars),
+                text: None,
+                reply: reply.map(str::to_string),
+                anonymous: false,
+                html: String::new(),
+                hidden: false,
+                order_id: None,
+                author: None,
+                author_id: None,
+                by_bot: false,
+                reply_by_bot: false,
+            }),
+            order_secrets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_review_templates() {
+        let templates = ReviewTemplates::new()
+            .with_reply(
+                5,
+                "Thanks, {buyer}! {stars}★ for {description} (#{order_id})",
+            )
+            .with_reply(3, "Sorry it was not perfect")
+            .with_escalate_below(3);
+
+        assert_eq!(
+            templates.action_for(&order(Some(5), None)),
+            ReviewReplyAction::Reply("Thanks, bob! 5★ for Gold (#ABCD1234)".to_string())
+        );
+        assert_eq!(
+            templates.action_for(&order(Some(1), None)),
+            ReviewReplyAction::Escalate
+        );
+        assert_eq!(
+            templates.action_for(&order(Some(4), None)),
+            ReviewReplyAction::Skip
+        );
+        assert_eq!(
+            templates.action_for(&order(Some(5), Some("done"))),
+            ReviewReplyAction::Skip
+        );
+        assert_eq!(
+            templates.action_for(&order(None, None)),
+            ReviewReplyAction::Skip
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+
+pub const EMPTY_TAG: &str = "00000000";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatNode {
+    pub node: String,
+    pub last_message: i64,
+    pub tag: String,
+}
+
+impl ChatNode {
+    pub fn new(node: impl Into<String>) -> Self {
+        Self {
+            node: node.into(),
+            last_message: -1,
+            tag: EMPTY_TAG.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatBookmarks {
+    pub user_id: i64,
+    pub tag: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrdersCounters {
+    pub user_id: i64,
+    pub tag: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CPUOnline {
+    pub user_id: i64,
+    pub tag: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunnerObject {
+    ChatNode(ChatNode),
+    ChatBookmarks(ChatBookmarks),
+    OrdersCounters(OrdersCounters),
+    CPUOnline(CPUOnline),
+}
+
+impl RunnerObject {
+    fn to_value(&self) -> Value {
+        match self {
+            RunnerObject::ChatNode(o) => json!({
+                "type": "chat_node",
+                "id": o.node,
+                "tag": o.tag,
+                "data": {"node": o.node, "last_message": o.last_message, "content": ""}
+            }),
+            RunnerObject::ChatBookmarks(o) => json!({
+                "type": "chat_bookmarks",
+                "id": o.user_id,
+                "tag": o.tag,
+                "data": false
+            }),
+            RunnerObject::OrdersCounters(o) => json!({
+                "type": "orders_counters",
+                "id": o.user_id,
+                "tag": o.tag,
+                "data": false
+            }),
+            RunnerObject::CPUOnline(o) => json!({
+                "type": "c-p-u",
+                "id": o.user_id,
+                "tag": o.tag,
+                "data": false
+            }),
+        }
+    }
+}
+
+impl Serialize for RunnerObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value().serialize(serializer)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunnerAction {
+    ChatMessage {
+        node: String,
+        last_message: i64,
+        content: String,
+    },
+    ChatImage {
+        node: String,
+        last_message: i64,
+        image_id: String,
+    },
+}
+
+impl RunnerAction {
+    fn to_value(&self) -> Value {
+        match self {
+            RunnerAction::ChatMessage {
+                node,
+                last_message,
+                content,
+            } => json!({
+                "action": "chat_message",
+                "data": {"node": node, "last_message": last_message, "content": content}
+            }),
+            RunnerAction::ChatImage {
+                node,
+                last_message,
+                image_id,
+            } => json!({
+                "action": "chat_message",
+                "data": {
+                    "node": node,
+                    "last_message": last_message,
+                    "content": "",
+                    "image_id": image_id
+                }
+            }),
+        }
+    }
+}
+
+impl Serialize for RunnerAction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value().serialize(serializer)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RunnerResponse {
+    #[serde(default)]
+    pub objects: Vec<RunnerObjectResult>,
+    #[serde(default, deserialize_with = "false_as_none")]
+    pub response: Option<ActionResponse>,
+}
+
+impl RunnerResponse {
+    pub fn chat_nodes(&self) -> impl Iterator<Item = (&str, Option<&ChatNodeData>)> {
+        self.objects.iter().filter_map(|obj| match obj {
+            RunnerObjectResult::ChatNode { id, data, .. } => Some((id.as_str(), data.as_ref())),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub enum RunnerObjectResult {
+    #[serde(rename = "chat_node")]
+    ChatNode {
+        #[serde(deserialize_with = "string_or_number")]
+        id: String,
+        #[serde(default)]
+        tag: Option<String>,
+        #[serde(default, deserialize_with = "false_as_none")]
+        data: Option<ChatNodeData>,
+    },
+    #[serde(rename = "chat_bookmarks")]
+    ChatBookmarks {
+        #[serde(default)]
+        tag: Option<String>,
+        #[serde(default, deserialize_with = "false_as_none")]
+        data: Option<HtmlData>,
+    },
+    #[serde(rename = "orders_counters")]
+    OrdersCounters {
+        #[serde(default)]
+        tag: Option<String>,
+        #[serde(default, deserialize_with = "false_as_none")]
+        data: Option<OrdersCountersData>,
+    },
+    #[serde(rename = "c-p-u")]
+    CPUOnline {
+        #[serde(deserialize_with = "string_or_number")]
+        id: String,
+        #[serde(default)]
+        tag: Option<String>,
+        #[serde(default, deserialize_with = "false_as_none")]
+        data: Option<Value>,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatNodeData {
+    #[serde(default)]
+    pub messages: Vec<RunnerMessage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatHistoryResponse {
+    #[serde(default, deserialize_with = "false_as_none")]
+    pub chat: Option<ChatNodeData>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RunnerMessage {
+    pub id: i64,
+    #[serde(default)]
+    pub author: i64,
+    #[serde(default)]
+    pub html: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct HtmlData {
+    #[serde(default)]
+    pub html: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OrdersCountersData {
+    #[serde(default)]
+    pub buyer: i32,
+    #[serde(default)]
+    pub seller: i32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ActionResponse {
+    #[serde(default, deserialize_with = "error_message")]
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+/// FunPay sends `false` instead of `null` for objects that did not change.
+fn false_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Null | Value::Bool(false) => Ok(None),
+        v => serde_json::from_value(v)
+            .map(Some)
+            .map_err(D::Error::custom),
+    }
+}
+
+fn string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        v => Err(D::Error::custom(format!(
+            "expected string or number, got {v}"
+        ))),
+    }
+}
+
+/// Normalizes FunPay's `error` fields: `false`/empty mean no error.
+pub(crate) fn error_message<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Null | Value::Bool(false) => Ok(None),
+        Value::Number(n) if n.as_i64() == Some(0) => Ok(None),
+        Value::String(s) if s.is_empty() => Ok(None),
+        Value::String(s) => Ok(Some(s)),
+        v => Ok(Some(v.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_runner_response() {
+        let body = r#"{
+            "objects": [
+                {"type": "orders_counters", "id": 1, "tag": "abc", "data": {"buyer": 2, "seller": 5}},
+                {"type": "chat_bookmarks", "id": 1, "tag": "def", "data": false},
+                {"type": "chat_node", "id": "users-1-2", "tag": "ghi", "data": {
+                    "node": {"id": 123, "name": "users-1-2"},
+                    "messages": [{"id": 10, "author": 2, "html": "<div class=\"chat-msg-text\">hi</div>"}]
+                }},
+                {"type": "something_new", "id": 1}
+            ],
+            "response": false
+        }"#;
+
+        let res: RunnerResponse = serde_json::from_str(body).unwrap();
+        assert_eq!(res.objects.len(), 4);
+        assert!(res.response.is_none());
+        assert!(matches!(
+            &res.objects[0],
+            RunnerObjectResult::OrdersCounters { data: Some(d), .. } if d.buyer == 2 && d.seller == 5
+        ));
+        assert!(matches!(
+            &res.objects[1],
+            RunnerObjectResult::ChatBookmarks { data: None, .. }
+        ));
+        assert!(matches!(&res.objects[3], RunnerObjectResult::Unknown));
+
+        let (node, data) = res.chat_nodes().next().unwrap();
+        assert_eq!(node, "users-1-2");
+        assert_eq!(data.unwrap().messages[0].id, 10);
+    }
+
+    #[test]
+    fn test_parse_runner_response_rejects_malformed_messages() {
+        let body = r#"{"objects": [{"type": "chat_node", "id": 5, "data": {"messages": [{"author": 1}]}}]}"#;
+        assert!(serde_json::from_str::<RunnerResponse>(body).is_err());
+    }
+}
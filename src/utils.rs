@@ -1,8 +1,15 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
 
 pub static RE_ORDER_ID: Lazy<Regex> = Lazy::new(|| Regex::new(r"#[A-Z0-9]{8}").unwrap());
 
+/// Invisible character prepended to messages sent by bots so they can be told
+/// apart from messages typed by a human on the same account.
+pub const BOT_MARKER: char = '\u{2064}';
+
 pub fn extract_phpsessid(set_cookies: &[String]) -> Option<String> {
     for val in set_cookies {
         if let Some(pos) = val.find("PHPSESSID=") {
@@ -24,3 +31,58 @@ pub fn random_tag() -> String {
         .map(|_| alphabet[rng.gen_range(0..alphabet.len())] as char)
         .collect()
 }
+
+/// Spaces requests at least `interval` apart across concurrent callers.
+/// Each caller reserves the next free slot and sleeps without holding the
+/// lock.
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        sleep_until(slot).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter_spacing() {
+        let limiter = RateLimiter::new(Duration::from_millis(40));
+        let start = Instant::now();
+        limiter.acquire().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        let mut second = Box::pin(limiter.acquire());
+        let mut third = Box::pin(limiter.acquire());
+        assert!(futures::poll!(&mut second).is_pending());
+        assert!(futures::poll!(&mut third).is_pending());
+        // The waiting callers do not keep the limiter locked.
+        assert!(limiter.next.try_lock().is_ok());
+
+        tokio::time::advance(Duration::from_millis(39)).await;
+        assert!(futures::poll!(&mut second).is_pending());
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert!(futures::poll!(&mut second).is_ready());
+        assert!(futures::poll!(&mut third).is_pending());
+        tokio::time::advance(Duration::from_millis(40)).await;
+        assert!(futures::poll!(&mut third).is_ready());
+    }
+}
@@ -0,0 +1,128 @@
+use crate::error::FunPayError;
+use crate::models::money::Currency;
+use crate::parsing::dates::funpay_now;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Limits for `FunPaySender::request_withdrawal`. Withdrawals are refused
+/// unless the sender carries a guard, the wallet is allowlisted for the
+/// method, and the amount fits into the currency's daily maximum. Totals are
+/// kept per FunPay (Moscow) calendar day and shared by every sender clone
+/// holding the same guard.
+#[derive(Debug, Default)]
+pub struct WithdrawalGuard {
+    daily_max: HashMap<Currency, Decimal>,
+    allowed_wallets: Vec<(String, String)>,
+    spent: Mutex<(Option<NaiveDate>, HashMap<Currency, Decimal>)>,
+}
+
+impl WithdrawalGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Currencies without a maximum cannot be withdrawn at all.
+    pub fn with_daily_max(mut self, currency: Currency, max: Decimal) -> Self {
+        self.daily_max.insert(currency, max);
+        self
+    }
+
+    /// Allows `wallet` for the FunPay payout `method` (e.g. `card_rub`).
+    pub fn with_allowed_wallet(
+        mut self,
+        method: impl Into<String>,
+        wallet: impl Into<String>,
+    ) -> Self {
+        self.allowed_wallets.push((method.into(), wallet.into()));
+        self
+    }
+
+    /// How much of today's maximum is still available for `currency`.
+    pub fn remaining_today(&self, currency: Currency) -> Decimal {
+        let max = self.daily_max.get(&currency).copied().unwrap_or_default();
+        let mut spent = self.spent.lock().unwrap();
+        Self::roll_day(&mut spent);
+        (max - spent.1.get(&currency).copied().unwrap_or_default()).max(Decimal::ZERO)
+    }
+
+    pub fn check_wallet(&self, method: &str, wallet: &str) -> Result<(), FunPayError> {
+        if self
+            .allowed_wallets
+            .iter()
+            .any(|(m, w)| m == method && w == wallet)
+        {
+            Ok(())
+        } else {
+            Err(FunPayError::WithdrawalDenied(format!(
+                "wallet {wallet} is not allowed for {method}"
+            )))
+        }
+    }
+
+    /// Counts `amount` against today's maximum, failing if it does not fit.
+    pub(crate) fn reserve(&self, currency: Currency, amount: Decimal) -> Result<(), FunPayError> {
+        if amount <= Decimal::ZERO {
+            return Err(FunPayError::WithdrawalDenied(format!(
+                "invalid amount {amount}"
+            )));
+        }
+        let Some(max) = self.daily_max.get(&currency).copied() else {
+            return Err(FunPayError::WithdrawalDenied(format!(
+                "no daily maximum set for {currency}"
+            )));
+        };
+        let mut spent = self.spent.lock().unwrap();
+        Self::roll_day(&mut spent);
+        let total = spent.1.entry(currency).or_default();
+        if *total + amount > max {
+            return Err(FunPayError::WithdrawalDenied(format!(
+                "{amount} {currency} exceeds today's remaining {}",
+                (max - *total).max(Decimal::ZERO)
+            )));
+        }
+        *total += amount;
+        Ok(())
+    }
+
+    /// Gives back a reservation for a withdrawal that did not go through.
+    pub(crate) fn release(&self, currency: Currency, amount: Decimal) {
+        let mut spent = self.spent.lock().unwrap();
+        if let Some(total) = spent.1.get_mut(&currency) {
+            *total = (*total - amount).max(Decimal::ZERO);
+        }
+    }
+
+    fn roll_day(spent: &mut (Option<NaiveDate>, HashMap<Currency, Decimal>)) {
+        let today = funpay_now().date_naive();
+        if spent.0 != Some(today) {
+            *spent = (Some(today), HashMap::new());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_withdrawal_guard() {
+        let guard = WithdrawalGuard::new()
+            .with_daily_max(Currency::Rub, Decimal::from(1000))
+            .with_allowed_wallet("card_rub", "2200000000000000");
+
+        assert!(guard.check_wallet("card_rub", "2200000000000000").is_ok());
+        assert!(guard.check_wallet("card_rub", "4100000000000000").is_err());
+        assert!(guard.check_wallet("fps", "2200000000000000").is_err());
+
+        assert!(guard.reserve(Currency::Rub, Decimal::from(600)).is_ok());
+        assert!(guard.reserve(Currency::Rub, Decimal::from(500)).is_err());
+        assert_eq!(guard.remaining_today(Currency::Rub), Decimal::from(400));
+        guard.release(Currency::Rub, Decimal::from(600));
+        assert!(guard.reserve(Currency::Rub, Decimal::from(1000)).is_ok());
+
+        assert!(guard.reserve(Currency::Usd, Decimal::ONE).is_err());
+        assert!(guard.reserve(Currency::Rub, Decimal::ZERO).is_err());
+    }
+}